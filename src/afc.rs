@@ -0,0 +1,80 @@
+use std::f32::consts::PI;
+use num_complex::Complex32;
+use crate::constants;
+
+/// Coarse automatic frequency control: a mixer ahead of the channel filter that removes the
+/// carrier offset of the previous packets.
+///
+/// The residual offset of a packet is measured after the discriminator, where the slicer
+/// threshold over the preamble is the offset in Hz. After each packet a fraction of it is
+/// moved into the mixer, so following packets of the same transmitter are centred in the filter.
+pub struct Afc {
+    sample_rate: f32,
+    frequency: f32, // coarse offset removed by the mixer (Hz)
+    phase: f32,
+}
+
+impl Afc {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            frequency: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// Coarse offset currently removed (Hz).
+    pub fn offset(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Mixes the IQ samples down by the coarse offset.
+    pub fn mix_down(&mut self, iq: &[Complex32]) -> Vec<Complex32> {
+        let step = 2.0 * PI * self.frequency / self.sample_rate;
+        iq.iter()
+            .map(|&sample| {
+                let mixed = sample * Complex32::from_polar(1.0, -self.phase);
+                self.phase = (self.phase + step) % (2.0 * PI);
+                mixed
+            })
+            .collect()
+    }
+
+    /// Moves the coarse offset towards the residual offset measured over a packet (Hz).
+    pub fn update(&mut self, residual: f32) {
+        let max = constants::AFC_MAX_OFFSET_HZ;
+        self.frequency = (self.frequency + constants::AFC_NCO_GAIN * residual).clamp(-max, max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_offset_to_dc() {
+        let sample_rate = 100_000.0;
+        let mut afc = Afc::new(sample_rate);
+        afc.update(5_000.0 / constants::AFC_NCO_GAIN);
+        assert!((afc.offset() - 5_000.0).abs() < 1e-3);
+
+        let tone: Vec<Complex32> = (0..1000)
+            .map(|n| Complex32::from_polar(1.0, 2.0 * PI * 5_000.0 * n as f32 / sample_rate))
+            .collect();
+        // Split across calls, the mixer phase carries on
+        let mut mixed = afc.mix_down(&tone[..317]);
+        mixed.extend(afc.mix_down(&tone[317..]));
+        for sample in mixed {
+            assert!((sample - Complex32::new(1.0, 0.0)).norm() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn offset_is_clamped() {
+        let mut afc = Afc::new(1e6);
+        for _ in 0..100 {
+            afc.update(1e6);
+        }
+        assert_eq!(afc.offset(), constants::AFC_MAX_OFFSET_HZ);
+    }
+}
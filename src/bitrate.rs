@@ -0,0 +1,224 @@
+use std::f32::consts::PI;
+use num_complex::Complex32;
+use crate::constants;
+
+/// HC-12 air data rates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitRate {
+    Rate500,
+    Rate5000,
+    Rate15000,
+    Rate58000,
+    Rate236000,
+    Rate250000,
+}
+
+impl BitRate {
+    pub const ALL: [BitRate; 6] = [BitRate::Rate500, BitRate::Rate5000, BitRate::Rate15000,
+                                   BitRate::Rate58000, BitRate::Rate236000, BitRate::Rate250000];
+
+    pub fn as_value(self) -> u32 {
+        match self {
+            BitRate::Rate500 => 500,
+            BitRate::Rate5000 => 5000,
+            BitRate::Rate15000 => 15000,
+            BitRate::Rate58000 => 58000,
+            BitRate::Rate236000 => 236000,
+            BitRate::Rate250000 => 250000,
+        }
+    }
+
+    pub fn as_string(self) -> String {
+        match self {
+            BitRate::Rate500 => "500".to_string(),
+            BitRate::Rate5000 => "5000".to_string(),
+            BitRate::Rate15000 => "15000".to_string(),
+            BitRate::Rate58000 => "58000".to_string(),
+            BitRate::Rate236000 => "236000".to_string(),
+            BitRate::Rate250000 => "250000".to_string(),
+        }
+    }
+
+    /// Nominal frequency deviation (Hz). The datasheet does not state it, these are typical
+    /// values; the rate detector reports the deviation actually measured on the preamble.
+    pub fn deviation(self) -> f32 {
+        match self {
+            BitRate::Rate500 => 2_500.0,
+            BitRate::Rate5000 => 10_000.0,
+            BitRate::Rate15000 => 15_000.0,
+            BitRate::Rate58000 => 30_000.0,
+            BitRate::Rate236000 => 120_000.0,
+            BitRate::Rate250000 => 125_000.0,
+        }
+    }
+}
+
+/// Air rate and deviation measured on the preamble of a burst.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitRateEstimate {
+    pub rate: BitRate,       // nearest HC-12 air rate
+    pub measured_rate: f32,  // 1 / measured symbol period (bps)
+    pub deviation: f32,      // measured peak frequency deviation (Hz)
+    pub score: f32,          // share of the preamble's frequency variance in its fundamental, 0..1
+}
+
+/// Classifies bursts by air rate. An alternating preamble makes the instantaneous frequency
+/// a periodic wave at half the bit rate, so for each candidate rate the share of the variance
+/// at that frequency is measured over `HC12_PREAMBLE_MIN_BITS` bits from the burst onset.
+/// The best candidate is refined to the measured symbol period, and its amplitude gives the deviation.
+pub struct BitRateDetector {
+    sample_rate: f32,
+}
+
+impl BitRateDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self { sample_rate }
+    }
+
+    /// Estimates the air rate from the leading samples of a burst, `None` if no candidate
+    /// rate explains the preamble.
+    pub fn estimate(&self, iq: &[Complex32]) -> Option<BitRateEstimate> {
+        let onset = Self::onset(iq)?;
+        let freq: Vec<f32> = iq[onset..].windows(2)
+            .map(|w| (w[1] * w[0].conj()).arg() * self.sample_rate / (2.0 * PI))
+            .collect();
+
+        let (candidate, score) = BitRate::ALL.iter()
+            .filter_map(|&rate| self.score(&freq, rate.as_value() as f32, rate.as_value() as f32).map(|s| (rate, s)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        if score < constants::BITRATE_MIN_SCORE {
+            return None;
+        }
+
+        // Search the main lobe around the nominal rate for the measured symbol period. Rates closer
+        // than the lobe width (236 and 250 kbps) are told apart by the measured period only.
+        let nominal = candidate.as_value() as f32;
+        let steps = constants::BITRATE_REFINE_STEPS as i32;
+        let measured_rate = (-steps..=steps)
+            .map(|k| nominal * (1.0 + constants::BITRATE_REFINE_SPAN * k as f32 / steps as f32))
+            .filter_map(|trial| self.score(&freq, nominal, trial).map(|s| (trial, s)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(nominal, |(trial, _)| trial);
+        let rate = BitRate::ALL.iter()
+            .copied()
+            .min_by(|a, b| {
+                let distance = |r: &BitRate| (measured_rate / r.as_value() as f32).ln().abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(candidate);
+
+        Some(BitRateEstimate {
+            rate,
+            measured_rate,
+            deviation: self.deviation(&freq, nominal, measured_rate),
+            score,
+        })
+    }
+
+    /// First sample from which the power stays near the burst level, so the analysis window
+    /// starts with the preamble rather than with the noise ahead of it.
+    fn onset(iq: &[Complex32]) -> Option<usize> {
+        let length = constants::BITRATE_ONSET_SAMPLES;
+        if iq.len() < length {
+            return None;
+        }
+        let mut powers: Vec<f32> = iq.iter().map(|s| s.norm_sqr()).collect();
+        let onset = powers.windows(length).map(|w| w.iter().sum::<f32>() / length as f32).collect::<Vec<f32>>();
+        let mid = powers.len() / 2;
+        let median = *powers.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1;
+        onset.iter().position(|&p| p > median / 2.0)
+    }
+
+    /// Frequency over `HC12_PREAMBLE_MIN_BITS` bits at the nominal rate, averaged down to
+    /// `BITRATE_SAMPLES_PER_BIT` values per bit to remove the wideband discriminator noise.
+    /// Returns the averaged window and the averaging length, `None` if the burst is too short.
+    fn window(&self, freq: &[f32], nominal_rate: f32) -> Option<(Vec<f32>, usize)> {
+        let samples_per_bit = self.sample_rate / nominal_rate;
+        let average = ((samples_per_bit / constants::BITRATE_SAMPLES_PER_BIT as f32).round() as usize).max(1);
+        let length = (constants::HC12_PREAMBLE_MIN_BITS as f32 * samples_per_bit).round() as usize;
+        if length < 4 * average || length > freq.len() {
+            return None;
+        }
+        let window = freq[..length]
+            .chunks_exact(average)
+            .map(|chunk| chunk.iter().sum::<f32>() / average as f32)
+            .collect();
+        Some((window, average))
+    }
+
+    /// Share of the window's variance at half of `rate`, 1.0 for a pure sine.
+    fn score(&self, freq: &[f32], nominal_rate: f32, rate: f32) -> Option<f32> {
+        let (window, average) = self.window(freq, nominal_rate)?;
+        let tone = Self::tone(&window, rate / 2.0 / self.sample_rate * average as f32);
+        let mean = window.iter().sum::<f32>() / window.len() as f32;
+        let variance = window.iter().map(|f| (f - mean).powi(2)).sum::<f32>() / window.len() as f32;
+        Some(tone.norm_sqr() / 2.0 / variance.max(f32::MIN_POSITIVE))
+    }
+
+    /// Peak deviation from the amplitude of the fundamental. A square wave's fundamental is 4/π
+    /// times its amplitude, the Gaussian filter and the averaging attenuate it further.
+    fn deviation(&self, freq: &[f32], nominal_rate: f32, rate: f32) -> f32 {
+        let Some((window, average)) = self.window(freq, nominal_rate) else {
+            return 0.0;
+        };
+        let f = rate / 2.0 / self.sample_rate;
+        let amplitude = Self::tone(&window, f * average as f32).norm();
+        let sigma = 2.0f32.ln().sqrt() / (2.0 * PI * constants::HC12_GAUSSIAN_BT);
+        let gaussian = (-(PI * sigma).powi(2) / 2.0).exp();
+        let boxcar = ((PI * f * average as f32).sin() / (average as f32 * (PI * f).sin())).abs();
+        amplitude * PI / 4.0 / gaussian / boxcar.max(f32::EPSILON)
+    }
+
+    /// Complex amplitude of the mean free window at normalised frequency `f` (cycles per sample).
+    fn tone(window: &[f32], f: f32) -> Complex32 {
+        let mean = window.iter().sum::<f32>() / window.len() as f32;
+        let sum: Complex32 = window.iter()
+            .enumerate()
+            .map(|(n, &x)| (x - mean) * Complex32::from_polar(1.0, -2.0 * PI * f * n as f32))
+            .sum();
+        sum * 2.0 / window.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FSK burst of alternating bits after some silence, `bits` long at `rate` bps.
+    fn preamble(sample_rate: f32, rate: f32, deviation: f32, bits: usize) -> Vec<Complex32> {
+        let silence = 200;
+        let length = (bits as f32 * sample_rate / rate) as usize;
+        let mut phase = 0.0f32;
+        let mut iq = vec![Complex32::new(0.0, 0.0); silence];
+        for n in 0..length {
+            let bit = (n as f32 * rate / sample_rate) as usize;
+            let frequency = if bit.is_multiple_of(2) { deviation } else { -deviation };
+            phase = (phase + 2.0 * PI * frequency / sample_rate) % (2.0 * PI);
+            iq.push(Complex32::from_polar(1.0, phase));
+        }
+        iq
+    }
+
+    #[test]
+    fn empty_or_short_burst() {
+        let detector = BitRateDetector::new(1_000_000.0);
+        assert_eq!(detector.estimate(&[]), None);
+        assert_eq!(detector.estimate(&[Complex32::new(1.0, 0.0); 3]), None);
+        assert_eq!(detector.estimate(&[Complex32::new(1.0, 0.0); 64]), None);
+    }
+
+    #[test]
+    fn classifies_15000_bps() {
+        let detector = BitRateDetector::new(280_000.0);
+        let estimate = detector.estimate(&preamble(280_000.0, 15_000.0, 15_000.0, 64)).unwrap();
+        assert_eq!(estimate.rate, BitRate::Rate15000);
+        assert!((estimate.measured_rate / 15_000.0 - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn classifies_250000_bps() {
+        let detector = BitRateDetector::new(2_400_000.0);
+        let estimate = detector.estimate(&preamble(2_400_000.0, 250_000.0, 125_000.0, 64)).unwrap();
+        assert_eq!(estimate.rate, BitRate::Rate250000);
+    }
+}
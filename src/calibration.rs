@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::constants;
+
+/// Crystal corrections by dongle serial number, kept in a text file of `serial ppm` lines.
+pub struct PpmStore {
+    path: PathBuf,
+    entries: BTreeMap<String, i32>,
+}
+
+impl PpmStore {
+    /// Reads the store, starting empty if the file does not exist yet. Malformed lines are skipped.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (serial, ppm) = line.trim().rsplit_once(char::is_whitespace)?;
+                Some((serial.trim().to_string(), ppm.parse().ok()?))
+            })
+            .collect();
+        Self { path, entries }
+    }
+
+    pub fn get(&self, serial: &str) -> Option<i32> {
+        self.entries.get(serial).copied()
+    }
+
+    /// Stores the correction for a dongle and writes the file.
+    pub fn set(&mut self, serial: &str, ppm: i32) -> Result<(), String> {
+        self.entries.insert(serial.to_string(), ppm);
+        let contents: String = self.entries.iter()
+            .map(|(serial, ppm)| format!("{} {}\n", serial, ppm))
+            .collect();
+        std::fs::write(&self.path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Outcome of a calibration run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub ppm: i32,       // correction to apply, including the one in effect while measuring
+    pub offset_hz: f32, // median carrier offset measured
+    pub spread_hz: f32, // range of the measured offsets
+}
+
+/// Measures the crystal error from the carrier offset of a transmitter known to sit on its
+/// nominal frequency. Each CRC-checked packet contributes the offset the decoder locked to;
+/// after `CALIBRATION_MEASUREMENTS` of them the median gives the correction.
+pub struct CalibrationWizard {
+    frequency: u32, // nominal frequency of the reference transmitter (Hz)
+    start_ppm: i32, // correction in effect while measuring
+    offsets: Vec<f32>,
+}
+
+impl CalibrationWizard {
+    pub fn new(frequency: u32, start_ppm: i32) -> Self {
+        Self {
+            frequency,
+            start_ppm,
+            offsets: Vec::new(),
+        }
+    }
+
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    pub fn add_measurement(&mut self, offset_hz: f32) {
+        if !self.is_complete() {
+            self.offsets.push(offset_hz);
+        }
+    }
+
+    pub fn measurements(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.offsets.len() >= constants::CALIBRATION_MEASUREMENTS
+    }
+
+    /// Correction once enough packets have been measured. A dongle whose crystal runs fast
+    /// tunes high, so the transmitter appears below the channel centre and the ppm goes up.
+    pub fn result(&self) -> Option<Calibration> {
+        if !self.is_complete() {
+            return None;
+        }
+        let mut offsets = self.offsets.clone();
+        offsets.sort_by(|a, b| a.total_cmp(b));
+        let offset_hz = offsets[offsets.len() / 2];
+        let error_ppm = -offset_hz / self.frequency as f32 * 1.0e6;
+        Some(Calibration {
+            ppm: self.start_ppm + error_ppm.round() as i32,
+            offset_hz,
+            spread_hz: offsets[offsets.len() - 1] - offsets[0],
+        })
+    }
+}
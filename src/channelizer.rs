@@ -0,0 +1,51 @@
+use std::f32::consts::PI;
+use num_complex::Complex32;
+use crate::decimator::{DecimationPlan, Decimator};
+
+/// One output channel: mixer, then the decimation chain.
+struct ChannelPath {
+    phasor: Complex32, // current mixer phase
+    step: Complex32,   // mixer rotation per input sample
+    decimator: Decimator,
+}
+
+/// Splits a wideband IQ stream into channels. Each channel is mixed to baseband by its own NCO,
+/// then filtered and decimated as the plan says. Without decimation the channels are only mixed
+/// down, the decoder's filter does the rest.
+pub struct Channelizer {
+    channels: Vec<ChannelPath>,
+}
+
+impl Channelizer {
+    /// `offsets` are the channel centres relative to the tuned frequency (Hz).
+    pub fn new(sample_rate: f32, offsets: &[f32], plan: &DecimationPlan) -> Self {
+        let channels = offsets.iter()
+            .map(|&offset| ChannelPath {
+                phasor: Complex32::new(1.0, 0.0),
+                step: Complex32::from_polar(1.0, -2.0 * PI * offset / sample_rate),
+                decimator: Decimator::new(plan),
+            })
+            .collect();
+
+        Self { channels }
+    }
+
+    /// Returns one stream per channel, at the plan's output rate.
+    pub fn process(&mut self, iq: &[Complex32]) -> Vec<Vec<Complex32>> {
+        self.channels.iter_mut()
+            .map(|channel| {
+                let mixed: Vec<Complex32> = iq.iter()
+                    .map(|&sample| {
+                        let mixed = sample * channel.phasor;
+                        channel.phasor *= channel.step;
+                        mixed
+                    })
+                    .collect();
+                // Keep the rotating phasor on the unit circle
+                channel.phasor /= channel.phasor.norm();
+
+                channel.decimator.process(&mixed)
+            })
+            .collect()
+    }
+}
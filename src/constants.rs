@@ -1,12 +1,212 @@
 
-/// Default sample rate for RTLSDR dongle.
-pub const SDR_SAMPLE_RATE: u32 = 280_000;
+/// Default sample rate for RTLSDR dongle, within its stable range of 1.024 to 2.4 MS/s.
+/// Each channel is decimated from it to a rate that suits the air rate.
+pub const SDR_SAMPLE_RATE: u32 = 2_048_000;
 
 /// Default center frequency
 pub const SDR_DEFAULT_CENTER_FREQUENCY: u32 = 460_200_000;
 
+/// Default offset of the dongle's tuning from the received channel (Hz), keeps the DC spike
+/// and LO leakage off the carrier. The channel is mixed back to baseband in software.
+pub const SDR_TUNING_OFFSET: i32 = 300_000;
+
+/// Centre frequency of HC-12 channel 1 (Hz).
+pub const HC12_CHANNEL_1_FREQUENCY: u32 = 433_400_000;
+
+/// Spacing between HC-12 channels (Hz).
+pub const HC12_CHANNEL_SPACING: u32 = 400_000;
+
+/// Highest HC-12 channel number.
+pub const HC12_MAX_CHANNEL: u8 = 127;
+
+/// Largest crystal correction accepted for the dongle (ppm).
+pub const SDR_MAX_PPM: i32 = 200;
+
+/// File the crystal corrections are kept in, one `serial ppm` line per dongle.
+pub const CALIBRATION_FILE: &str = "hc12_rtlsdr_ppm.txt";
+
+/// Packets measured by the calibration wizard before it computes the correction.
+pub const CALIBRATION_MEASUREMENTS: usize = 10;
+
 /// Default gain setting fot the RTLSDR dongle
 pub const SDR_DEFAULT_GAIN: i32 = 300;
 
 /// Default  buffersize for IQ asynchronous read
-pub const SDR_BUFFER_SIZE: usize = 0x20000;
\ No newline at end of file
+pub const SDR_BUFFER_SIZE: usize = 0x20000;
+
+/// Time spent decoding the blocks that are due in each GUI frame (s). A source faster than
+/// the decoder falls behind rather than freezing the GUI.
+pub const PROCESSING_BUDGET_S: f32 = 0.05;
+
+/// Default `host:port` of an rtl_tcp server.
+pub const RTL_TCP_DEFAULT_ADDRESS: &str = "127.0.0.1:1234";
+
+/// Time allowed to connect to an rtl_tcp server (s).
+pub const RTL_TCP_CONNECT_TIMEOUT_S: f32 = 3.0;
+
+/// Time between the packets of the synthetic HC-12 transmitter (s).
+pub const SYNTHETIC_PACKET_INTERVAL_S: f32 = 0.5;
+
+/// Level of the synthetic transmitter's carrier (dBFS).
+pub const SYNTHETIC_SIGNAL_DBFS: f32 = -20.0;
+
+/// Level of the synthetic source's noise over the full sample rate (dBFS).
+pub const SYNTHETIC_NOISE_DBFS: f32 = -40.0;
+
+/// Time after the end of an annotated packet by which the decoder has to report it, or the
+/// annotation counts as missed (s). Covers the squelch hangover and the filter delays.
+pub const REFERENCE_REPORT_DELAY_S: f32 = 0.2;
+
+/// Default SI4463 sync word as configured by the HC-12 firmware (SI446x reset value).
+pub const HC12_DEFAULT_SYNC_WORD: u32 = 0x2DD4;
+
+/// Length of the default sync word in bits.
+pub const HC12_DEFAULT_SYNC_BITS: usize = 16;
+
+/// Minimum number of alternating 0xAA/0x55 preamble bits before the sync word is searched.
+pub const HC12_PREAMBLE_MIN_BITS: usize = 16;
+
+/// Largest payload accepted from the length field (size of the SI4463 FIFO).
+pub const HC12_MAX_PAYLOAD_LENGTH: usize = 64;
+
+/// Largest payload per packet in FU4, longer serial input is split by the module.
+pub const HC12_FU4_MAX_PAYLOAD_LENGTH: usize = 60;
+
+/// Serial baud rates supported by the HC-12 (`AT+Bxxxx`).
+pub const HC12_SERIAL_BAUD_RATES: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+
+/// Factory default serial baud rate.
+pub const HC12_DEFAULT_SERIAL_BAUD: u32 = 9600;
+
+/// Number of decoded packets kept in the GUI packet log.
+pub const PACKET_LOG_SIZE: usize = 200;
+
+/// Reset value of the SI4463 PN9 whitening LFSR.
+pub const HC12_PN9_DEFAULT_SEED: u16 = 0x1FF;
+
+/// Normalised noise bandwidth (Bn·Ts) of the symbol timing loop.
+pub const TIMING_LOOP_BANDWIDTH: f32 = 0.02;
+
+/// Damping factor of the symbol timing loop.
+pub const TIMING_LOOP_DAMPING: f32 = 0.707;
+
+/// Samples per symbol after resampling the discriminator output ahead of timing recovery.
+pub const RESAMPLED_SAMPLES_PER_SYMBOL: usize = 8;
+
+/// Filter taps per polyphase branch of the resampler.
+pub const RESAMPLER_TAPS_PER_PHASE: usize = 16;
+
+/// Largest interpolation factor of the resampler before the rate ratio is approximated.
+pub const RESAMPLER_MAX_INTERPOLATION: usize = 512;
+
+/// Length of the channel low-pass filter kernel.
+pub const LOWPASS_NUM_TAPS: usize = 259;
+
+/// Kernel length from which the low-pass filter uses FFT overlap-save convolution.
+pub const FIR_FFT_MIN_TAPS: usize = 64;
+
+/// Fraction of a packet's measured offset moved into the coarse mixer afterwards.
+pub const AFC_NCO_GAIN: f32 = 0.5;
+
+/// Offset the channel filter passes beyond the signal bandwidth before the coarse mixer has converged (Hz).
+pub const AFC_PULL_IN_HZ: f32 = 10_000.0;
+
+/// Largest carrier offset corrected by the coarse mixer (Hz), about 55 ppm at 433 MHz.
+pub const AFC_MAX_OFFSET_HZ: f32 = 24_000.0;
+
+/// Bandwidth-time product of the SI4463 GFSK Gaussian filter.
+pub const HC12_GAUSSIAN_BT: f32 = 0.5;
+
+/// Half length of the Gaussian matched filter beyond the symbol, in standard deviations.
+pub const GAUSSIAN_SPAN_SIGMAS: f32 = 3.0;
+
+/// Samples after which the quadrature correlator recomputes its running sums.
+pub const CORRELATOR_REFRESH_INTERVAL: usize = 65_536;
+
+/// Loop gain of the decision directed slicer level tracking.
+pub const SLICER_TRACK_GAIN: f32 = 0.02;
+
+/// Soft values below this magnitude count as weak bits.
+pub const SOFT_WEAK_THRESHOLD: f32 = 0.3;
+
+/// Estimated bit error probability above which a packet is flagged as marginal.
+pub const MARGINAL_BIT_ERROR_PROBABILITY: f32 = 1.0e-4;
+
+/// Number of samples averaged into one power measurement.
+pub const POWER_BLOCK_SIZE: usize = 64;
+
+/// Power measurements kept to look up the level of a packet after it has been decoded.
+pub const POWER_HISTORY_BLOCKS: usize = 8192;
+
+/// Percentile of the recent block powers taken as noise floor.
+pub const NOISE_FLOOR_PERCENTILE: f32 = 0.1;
+
+/// Rough dBm of a 0 dBFS signal at 0 dB tuner gain, adjust per dongle.
+pub const RSSI_CALIBRATION_DB: f32 = -10.0;
+
+/// Default power above the noise floor that opens the squelch (dB).
+pub const SQUELCH_DEFAULT_MARGIN_DB: f32 = 6.0;
+
+/// Amount the power has to fall below the opening level before a burst may close (dB).
+pub const SQUELCH_HYSTERESIS_DB: f32 = 3.0;
+
+/// Loop gain of the squelch noise floor, per block while the squelch is closed.
+pub const SQUELCH_FLOOR_GAIN: f32 = 0.01;
+
+/// Samples passed to the decoder ahead of a burst so its filters have settled (s).
+pub const SQUELCH_PREROLL_S: f32 = 0.002;
+
+/// Time below the closing level after which a burst ends (s).
+pub const SQUELCH_HANGOVER_S: f32 = 0.002;
+
+/// Longest burst before the squelch assumes the noise floor has risen (s).
+pub const SQUELCH_MAX_BURST_S: f32 = 1.0;
+
+/// Number of bursts kept in the GUI activity log.
+pub const BURST_LOG_SIZE: usize = 200;
+
+/// Leading samples of each burst kept for the air rate estimate (s), 20 bits at 500 bps.
+pub const BURST_CAPTURE_S: f32 = 0.04;
+
+/// Fraction of the preamble's frequency variance at half the bit rate needed to accept a rate.
+pub const BITRATE_MIN_SCORE: f32 = 0.5;
+
+/// Relative span searched around the classified rate to measure the symbol period.
+pub const BITRATE_REFINE_SPAN: f32 = 0.1;
+
+/// Rates tried on each side of the classified rate within `BITRATE_REFINE_SPAN`.
+pub const BITRATE_REFINE_STEPS: usize = 20;
+
+/// Values per bit the frequency is averaged down to before the preamble is analysed.
+pub const BITRATE_SAMPLES_PER_BIT: usize = 4;
+
+/// Moving average length used to find the onset of a burst.
+pub const BITRATE_ONSET_SAMPLES: usize = 8;
+
+/// Relative difference between the measured deviation and the decoder's at which the decoder is set up again.
+pub const BITRATE_DEVIATION_TOLERANCE: f32 = 0.2;
+
+/// Sample rate of the dongle when several channels are received at once.
+pub const CHANNELIZER_SAMPLE_RATE: u32 = 2_400_000;
+
+/// Number of adjacent channels received at `CHANNELIZER_SAMPLE_RATE`.
+pub const CHANNELIZER_CHANNELS: usize = 6;
+
+/// Highest frequency either side of a channel's centre that its decimation filters pass flat,
+/// as a fraction of the channel sample rate.
+pub const DECIMATION_PASSBAND: f32 = 0.4;
+
+/// Half-band stages at the end of the decimation chain.
+pub const DECIMATION_MAX_HALF_BANDS: usize = 2;
+
+/// Taps of the half-band filters, of the form 4k + 3 so both ends are non-zero.
+pub const DECIMATION_HALF_BAND_TAPS: usize = 39;
+
+/// Integrator and comb pairs of the CIC decimator.
+pub const DECIMATION_CIC_STAGES: usize = 4;
+
+/// Gain of the front end's DC tracker per sample, sets the width of the notch at DC.
+pub const FRONTEND_DC_ALPHA: f32 = 1.0e-4;
+
+/// Gain of the front end's IQ imbalance estimator per sample.
+pub const FRONTEND_IQ_ALPHA: f32 = 1.0e-5;
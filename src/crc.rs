@@ -0,0 +1,251 @@
+/// CRC polynomials offered by the SI4463 packet handler (PKT_CRC_CONFIG).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrcPolynomial {
+    None,
+    ItuT8,
+    Iec16,
+    Baicheva16,
+    Ibm16,
+    Ccitt16,
+    Koopman32,
+    Ieee8023,
+    Castagnoli32,
+    Dnp16,
+}
+
+impl CrcPolynomial {
+    pub const ALL: [CrcPolynomial; 10] = [
+        CrcPolynomial::None,
+        CrcPolynomial::ItuT8,
+        CrcPolynomial::Iec16,
+        CrcPolynomial::Baicheva16,
+        CrcPolynomial::Ibm16,
+        CrcPolynomial::Ccitt16,
+        CrcPolynomial::Koopman32,
+        CrcPolynomial::Ieee8023,
+        CrcPolynomial::Castagnoli32,
+        CrcPolynomial::Dnp16,
+    ];
+
+    /// Generator polynomial in normal (MSB first) notation, without the leading term.
+    pub fn as_value(self) -> u32 {
+        match self {
+            CrcPolynomial::None => 0,
+            CrcPolynomial::ItuT8 => 0x07,
+            CrcPolynomial::Iec16 => 0x5B93,
+            CrcPolynomial::Baicheva16 => 0x90D9,
+            CrcPolynomial::Ibm16 => 0x8005,
+            CrcPolynomial::Ccitt16 => 0x1021,
+            CrcPolynomial::Koopman32 => 0x741B_8CD7,
+            CrcPolynomial::Ieee8023 => 0x04C1_1DB7,
+            CrcPolynomial::Castagnoli32 => 0x1EDC_6F41,
+            CrcPolynomial::Dnp16 => 0x3D65,
+        }
+    }
+
+    /// CRC width in bits, 0 when CRC is disabled.
+    pub fn width(self) -> usize {
+        match self {
+            CrcPolynomial::None => 0,
+            CrcPolynomial::ItuT8 => 8,
+            CrcPolynomial::Iec16
+            | CrcPolynomial::Baicheva16
+            | CrcPolynomial::Ibm16
+            | CrcPolynomial::Ccitt16
+            | CrcPolynomial::Dnp16 => 16,
+            CrcPolynomial::Koopman32
+            | CrcPolynomial::Ieee8023
+            | CrcPolynomial::Castagnoli32 => 32,
+        }
+    }
+
+    pub fn as_string(self) -> String {
+        match self {
+            CrcPolynomial::None => "None".to_string(),
+            CrcPolynomial::ItuT8 => "ITU-T CRC-8".to_string(),
+            CrcPolynomial::Iec16 => "IEC-16".to_string(),
+            CrcPolynomial::Baicheva16 => "Baicheva-16".to_string(),
+            CrcPolynomial::Ibm16 => "CRC-16 IBM".to_string(),
+            CrcPolynomial::Ccitt16 => "CCITT-16".to_string(),
+            CrcPolynomial::Koopman32 => "Koopman-32".to_string(),
+            CrcPolynomial::Ieee8023 => "IEEE 802.3 CRC-32".to_string(),
+            CrcPolynomial::Castagnoli32 => "Castagnoli-32".to_string(),
+            CrcPolynomial::Dnp16 => "CRC-16 DNP".to_string(),
+        }
+    }
+}
+
+/// Order in which the bits of each byte enter the CRC register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrcBitOrder {
+    /// Bytes are processed MSB first, the CRC is transmitted high byte first.
+    MsbFirst,
+    /// Bytes and result are bit reflected, the CRC is transmitted low byte first.
+    LsbFirst,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrcConfig {
+    pub polynomial: CrcPolynomial,
+    pub seed: u32,          // initial register value
+    pub bit_order: CrcBitOrder,
+    pub invert: bool,       // complement the final CRC value
+}
+
+impl Default for CrcConfig {
+    fn default() -> Self {
+        Self {
+            polynomial: CrcPolynomial::Ccitt16,
+            seed: 0xFFFF,
+            bit_order: CrcBitOrder::MsbFirst,
+            invert: false,
+        }
+    }
+}
+
+/// Result of the CRC check of a received packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrcStatus {
+    Ok,
+    Failed,
+    Disabled,
+}
+
+impl CrcStatus {
+    pub fn as_string(self) -> String {
+        match self {
+            CrcStatus::Ok => "CRC OK".to_string(),
+            CrcStatus::Failed => "CRC FAIL".to_string(),
+            CrcStatus::Disabled => "no CRC".to_string(),
+        }
+    }
+}
+
+impl CrcConfig {
+    /// Number of CRC bytes appended to each packet.
+    pub fn num_bytes(&self) -> usize {
+        self.polynomial.width() / 8
+    }
+
+    /// Calculates the CRC over `data` with a bitwise shift register.
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        let width = self.polynomial.width();
+        if width == 0 {
+            return 0;
+        }
+        let mask = if width == 32 { u32::MAX } else { (1u32 << width) - 1 };
+        let poly = self.polynomial.as_value();
+        let reflect = self.bit_order == CrcBitOrder::LsbFirst;
+
+        let mut crc = self.seed & mask;
+        for &byte in data {
+            let byte = if reflect { byte.reverse_bits() } else { byte };
+            for i in (0..8).rev() {
+                let feedback = ((crc >> (width - 1)) & 1) ^ ((byte >> i) & 1) as u32;
+                crc = (crc << 1) & mask;
+                if feedback != 0 {
+                    crc ^= poly;
+                }
+            }
+        }
+
+        if reflect {
+            crc = crc.reverse_bits() >> (32 - width);
+        }
+        if self.invert {
+            crc ^= mask;
+        }
+        crc
+    }
+
+    /// Checks `data` against the CRC bytes as they were received over the air.
+    pub fn check(&self, data: &[u8], received: &[u8]) -> CrcStatus {
+        if self.polynomial == CrcPolynomial::None {
+            return CrcStatus::Disabled;
+        }
+        let value = match self.bit_order {
+            CrcBitOrder::MsbFirst => received.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32),
+            CrcBitOrder::LsbFirst => received.iter().rev().fold(0u32, |acc, &b| (acc << 8) | b as u32),
+        };
+        if value == self.compute(data) {
+            CrcStatus::Ok
+        } else {
+            CrcStatus::Failed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK_INPUT: &[u8] = b"123456789";
+
+    fn config(polynomial: CrcPolynomial, seed: u32, bit_order: CrcBitOrder, invert: bool) -> CrcConfig {
+        CrcConfig { polynomial, seed, bit_order, invert }
+    }
+
+    /// CRC bytes in the order they are sent over the air.
+    fn transmitted(config: &CrcConfig, crc: u32) -> Vec<u8> {
+        let bytes: Vec<u8> = (0..config.num_bytes()).rev().map(|i| (crc >> (8 * i)) as u8).collect();
+        match config.bit_order {
+            CrcBitOrder::MsbFirst => bytes,
+            CrcBitOrder::LsbFirst => bytes.into_iter().rev().collect(),
+        }
+    }
+
+    #[test]
+    fn check_values() {
+        use CrcBitOrder::{LsbFirst, MsbFirst};
+        use CrcPolynomial::*;
+        // Parameter sets of the CRC catalogue where the polynomial has an entry there, otherwise
+        // seed 0, MSB first, not inverted
+        let cases = [
+            (config(ItuT8, 0x00, MsbFirst, false), 0xF4),              // CRC-8/SMBUS
+            (config(Iec16, 0x0000, MsbFirst, false), 0x537C),
+            (config(Baicheva16, 0x0000, MsbFirst, false), 0x913A),
+            (config(Ibm16, 0x0000, LsbFirst, false), 0xBB3D),          // CRC-16/ARC
+            (config(Ibm16, 0x0000, MsbFirst, false), 0xFEE8),          // CRC-16/UMTS
+            (config(Ccitt16, 0xFFFF, MsbFirst, false), 0x29B1),        // CRC-16/IBM-3740
+            (config(Ccitt16, 0x0000, LsbFirst, false), 0x2189),        // CRC-16/KERMIT
+            (config(Ccitt16, 0xFFFF, MsbFirst, true), 0xD64E),         // CRC-16/GENIBUS
+            (config(Ccitt16, 0xFFFF, LsbFirst, true), 0x906E),         // CRC-16/IBM-SDLC
+            (config(Koopman32, 0x0000_0000, MsbFirst, false), 0x085A_3197),
+            (config(Ieee8023, 0xFFFF_FFFF, LsbFirst, true), 0xCBF4_3926), // CRC-32/ISO-HDLC
+            (config(Ieee8023, 0xFFFF_FFFF, MsbFirst, true), 0xFC89_1918), // CRC-32/BZIP2
+            (config(Castagnoli32, 0xFFFF_FFFF, LsbFirst, true), 0xE306_9283), // CRC-32/ISCSI
+            (config(Dnp16, 0x0000, LsbFirst, true), 0xEA82),           // CRC-16/DNP
+            (config(Dnp16, 0x0000, MsbFirst, true), 0xC2B7),           // CRC-16/EN-13757
+        ];
+        for (config, check) in &cases {
+            assert_eq!(config.compute(CHECK_INPUT), *check, "{:?}", config);
+        }
+        for polynomial in CrcPolynomial::ALL.into_iter().filter(|&p| p != CrcPolynomial::None) {
+            assert!(cases.iter().any(|(config, _)| config.polynomial == polynomial), "{} has no check value", polynomial.as_string());
+        }
+    }
+
+    #[test]
+    fn disabled() {
+        let config = config(CrcPolynomial::None, 0xFFFF, CrcBitOrder::MsbFirst, false);
+        assert_eq!(config.num_bytes(), 0);
+        assert_eq!(config.compute(CHECK_INPUT), 0);
+        assert_eq!(config.check(CHECK_INPUT, &[]), CrcStatus::Disabled);
+    }
+
+    #[test]
+    fn compute_then_check() {
+        let data = [0x05, b'H', b'C', b'-', b'1', b'2'];
+        for polynomial in CrcPolynomial::ALL.into_iter().filter(|&p| p != CrcPolynomial::None) {
+            for bit_order in [CrcBitOrder::MsbFirst, CrcBitOrder::LsbFirst] {
+                for invert in [false, true] {
+                    let config = config(polynomial, 0x1D0F, bit_order, invert);
+                    let mut crc = transmitted(&config, config.compute(&data));
+                    assert_eq!(config.check(&data, &crc), CrcStatus::Ok, "{:?}", config);
+                    crc[0] ^= 0x01;
+                    assert_eq!(config.check(&data, &crc), CrcStatus::Failed, "{:?}", config);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use num_complex::Complex32;
+use crate::bitrate::BitRate;
+use crate::constants;
+use crate::hc12_decoder::LowPassFilter;
+
+/// Rate change from the device to a channel: a CIC stage takes the bulk of it, the half-band
+/// stages that follow flatten the passband and reject what the CIC lets alias.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecimationPlan {
+    pub cic: usize,        // CIC decimation, 1 for none
+    pub half_bands: usize, // half-band stages, each decimating by 2
+}
+
+impl DecimationPlan {
+    /// No decimation, the channel runs at the device rate.
+    pub const NONE: DecimationPlan = DecimationPlan { cic: 1, half_bands: 0 };
+
+    /// Largest decimation that keeps `bandwidth` (Hz either side of the channel centre) within
+    /// `DECIMATION_PASSBAND` of the output rate.
+    pub fn for_bandwidth(sample_rate: f32, bandwidth: f32) -> Self {
+        let min_rate = bandwidth / constants::DECIMATION_PASSBAND;
+        let max_decimation = ((sample_rate / min_rate) as usize).max(1);
+        let half_bands = (max_decimation.ilog2() as usize).min(constants::DECIMATION_MAX_HALF_BANDS);
+        Self {
+            cic: max_decimation >> half_bands,
+            half_bands,
+        }
+    }
+
+    /// Plan for an air rate: its nominal deviation and half the bit rate, plus the AFC pull-in range.
+    pub fn for_air_rate(sample_rate: f32, rate: BitRate) -> Self {
+        let bandwidth = rate.deviation() + rate.as_value() as f32 / 2.0 + constants::AFC_PULL_IN_HZ;
+        Self::for_bandwidth(sample_rate, bandwidth)
+    }
+
+    pub fn decimation(&self) -> usize {
+        self.cic << self.half_bands
+    }
+
+    pub fn output_rate(&self, sample_rate: f32) -> f32 {
+        sample_rate / self.decimation() as f32
+    }
+
+    pub fn as_string(&self) -> String {
+        match (self.cic, self.half_bands) {
+            (1, 0) => "none".to_string(),
+            (1, h) => format!("{} half-band", h),
+            (r, 0) => format!("CIC ÷{}", r),
+            (r, h) => format!("CIC ÷{} + {} half-band", r, h),
+        }
+    }
+}
+
+/// FIR filter evaluated only at its decimated outputs, skipping zero coefficients.
+struct DecimatingFir {
+    taps: Vec<(usize, f32)>, // non-zero coefficients and their delay
+    history: Vec<Complex32>, // last input samples of the previous buffer
+    decimation: usize,
+    next: usize,             // index of the next output, relative to the current buffer
+}
+
+impl DecimatingFir {
+    fn new(kernel: &[f32], decimation: usize) -> Self {
+        Self {
+            taps: kernel.iter()
+                .enumerate()
+                .filter(|(_, h)| h.abs() > 1e-6)
+                .map(|(j, &h)| (j, h))
+                .collect(),
+            history: vec![Complex32::new(0.0, 0.0); kernel.len() - 1],
+            decimation,
+            next: 0,
+        }
+    }
+
+    fn process(&mut self, iq: &[Complex32]) -> Vec<Complex32> {
+        let delay = self.history.len();
+        let mut buffer = Vec::with_capacity(delay + iq.len());
+        buffer.extend_from_slice(&self.history);
+        buffer.extend_from_slice(iq);
+
+        let mut output = Vec::with_capacity(iq.len() / self.decimation + 1);
+        while self.next < iq.len() {
+            let n = delay + self.next;
+            output.push(self.taps.iter().fold(Complex32::new(0.0, 0.0), |acc, &(j, h)| acc + buffer[n - j] * h));
+            self.next += self.decimation;
+        }
+
+        self.next -= iq.len();
+        self.history.copy_from_slice(&buffer[buffer.len() - delay..]);
+        output
+    }
+}
+
+/// Runs a `DecimationPlan` on a stream. The CIC is evaluated in its non-recursive form, as the
+/// FIR its integrators and combs amount to, which avoids the integrators' unbounded growth in
+/// floating point.
+pub struct Decimator {
+    stages: Vec<DecimatingFir>,
+}
+
+impl Decimator {
+    pub fn new(plan: &DecimationPlan) -> Self {
+        let mut stages = Vec::new();
+        if plan.cic > 1 {
+            stages.push(DecimatingFir::new(&Self::cic_kernel(plan.cic), plan.cic));
+        }
+        let half_band = LowPassFilter::build_kernel(2.0, 0.5, constants::DECIMATION_HALF_BAND_TAPS);
+        for _ in 0..plan.half_bands {
+            stages.push(DecimatingFir::new(&half_band, 2));
+        }
+        Self { stages }
+    }
+
+    pub fn process(&mut self, iq: &[Complex32]) -> Vec<Complex32> {
+        self.stages.iter_mut().fold(iq.to_vec(), |samples, stage| stage.process(&samples))
+    }
+
+    /// Impulse response of a CIC filter: `DECIMATION_CIC_STAGES` boxcars of length `decimation`
+    /// convolved, normalised to unity DC gain.
+    fn cic_kernel(decimation: usize) -> Vec<f32> {
+        let boxcar = vec![1.0f32; decimation];
+        let mut kernel = vec![1.0f32];
+        for _ in 0..constants::DECIMATION_CIC_STAGES {
+            let mut next = vec![0.0f32; kernel.len() + boxcar.len() - 1];
+            for (i, &k) in kernel.iter().enumerate() {
+                for (j, &b) in boxcar.iter().enumerate() {
+                    next[i + j] += k * b;
+                }
+            }
+            kernel = next;
+        }
+        let sum: f32 = kernel.iter().sum();
+        kernel.iter().map(|k| k / sum).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_reaches_target_rate() {
+        let sample_rate = 2_048_000.0;
+        for rate in BitRate::ALL {
+            let plan = DecimationPlan::for_air_rate(sample_rate, rate);
+            let min_rate = (rate.deviation() + rate.as_value() as f32 / 2.0 + constants::AFC_PULL_IN_HZ) / constants::DECIMATION_PASSBAND;
+            assert_eq!(plan.decimation(), plan.cic << plan.half_bands);
+            assert!(plan.half_bands <= constants::DECIMATION_MAX_HALF_BANDS);
+            assert!(plan.output_rate(sample_rate) >= min_rate, "{rate:?}: {}", plan.as_string());
+            // one more CIC step would be too far
+            assert!(min_rate > sample_rate / ((plan.cic + 1) << plan.half_bands) as f32, "{rate:?}: {}", plan.as_string());
+        }
+        assert_eq!(DecimationPlan::for_bandwidth(sample_rate, sample_rate), DecimationPlan::NONE);
+    }
+
+    #[test]
+    fn unity_dc_gain() {
+        let input = vec![Complex32::new(0.5, -0.25); 20000];
+        let plans = [
+            DecimationPlan::NONE,
+            DecimationPlan { cic: 5, half_bands: 0 },
+            DecimationPlan { cic: 1, half_bands: 2 },
+            DecimationPlan { cic: 8, half_bands: 2 },
+        ];
+        for plan in plans {
+            let mut decimator = Decimator::new(&plan);
+            let output: Vec<Complex32> = input.chunks(4096).flat_map(|chunk| decimator.process(chunk)).collect();
+            assert_eq!(output.len(), input.len() / plan.decimation(), "{}", plan.as_string());
+            for value in &output[output.len() / 2..] {
+                assert!((value - input[0]).norm() < 1e-3, "{}: {value}", plan.as_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use num_complex::Complex32;
+use crate::constants;
+
+/// FSK detector feeding symbol timing recovery.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detector {
+    /// Phase difference between consecutive samples, optionally followed by the Gaussian matched filter.
+    Discriminator,
+    /// Non-coherent two-tone correlator, compares the energy at +deviation and -deviation per symbol.
+    QuadratureCorrelator,
+}
+
+impl Detector {
+    pub const ALL: [Detector; 2] = [Detector::Discriminator, Detector::QuadratureCorrelator];
+
+    pub fn as_string(self) -> String {
+        match self {
+            Detector::Discriminator => "Discriminator".to_string(),
+            Detector::QuadratureCorrelator => "Quadrature correlator".to_string(),
+        }
+    }
+}
+
+/// Demodulator settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemodConfig {
+    pub detector: Detector,
+    pub matched_filter: bool, // Gaussian matched filter after the discriminator
+    pub gaussian_bt: f32,     // bandwidth-time product of the transmit Gaussian filter
+}
+
+impl Default for DemodConfig {
+    fn default() -> Self {
+        Self {
+            detector: Detector::Discriminator,
+            matched_filter: true,
+            gaussian_bt: constants::HC12_GAUSSIAN_BT,
+        }
+    }
+}
+
+/// Builds the GFSK frequency pulse (a one symbol rectangle smoothed by the Gaussian filter),
+/// used as matched filter for the discriminator output. Odd length, unity DC gain.
+pub fn gaussian_pulse(samples_per_symbol: f32, bt: f32) -> Vec<f32> {
+    let sigma = (2.0f32.ln()).sqrt() / (2.0 * PI * bt.max(0.05)) * samples_per_symbol;
+    let rect_half = samples_per_symbol / 2.0;
+    let half = (rect_half + constants::GAUSSIAN_SPAN_SIGMAS * sigma).ceil() as i32;
+
+    let mut h: Vec<f32> = (-half..=half)
+        .map(|n| {
+            // Gaussian integrated over the rectangle [-T/2, T/2]
+            let steps = samples_per_symbol.ceil().max(1.0) as i32 * 4;
+            (0..=steps)
+                .map(|k| {
+                    let t = n as f32 - (-rect_half + 2.0 * rect_half * k as f32 / steps as f32);
+                    (-t * t / (2.0 * sigma * sigma)).exp()
+                })
+                .sum::<f32>()
+        })
+        .collect();
+
+    let sum: f32 = h.iter().sum();
+    h.iter_mut().for_each(|v| *v /= sum);
+    h
+}
+
+/// Quadrature (two-tone) correlator. Each input sample is mixed with both tones and
+/// integrated over one symbol, the output is the normalised energy difference scaled to the
+/// deviation, so it can be treated like a frequency by the following stages.
+pub struct QuadratureCorrelator {
+    step: f32,                            // tone phase increment per sample
+    phase: f32,
+    length: usize,                        // integration length (one symbol)
+    mark: VecDeque<Complex32>,            // mixed samples in the window, +deviation tone
+    space: VecDeque<Complex32>,           // mixed samples in the window, -deviation tone
+    mark_sum: Complex32,
+    space_sum: Complex32,
+    since_refresh: usize,                 // samples since the running sums were recomputed
+    deviation: f32,
+}
+
+impl QuadratureCorrelator {
+    pub fn new(sample_rate: f32, samples_per_symbol: f32, deviation: f32) -> Self {
+        let length = samples_per_symbol.round().max(1.0) as usize;
+        Self {
+            step: 2.0 * PI * deviation / sample_rate,
+            phase: 0.0,
+            length,
+            mark: VecDeque::with_capacity(length + 1),
+            space: VecDeque::with_capacity(length + 1),
+            mark_sum: Complex32::new(0.0, 0.0),
+            space_sum: Complex32::new(0.0, 0.0),
+            since_refresh: 0,
+            deviation,
+        }
+    }
+
+    /// Group delay of the integrator in samples.
+    pub fn delay(&self) -> usize {
+        self.length / 2
+    }
+
+    pub fn process(&mut self, iq: &[Complex32]) -> Vec<f32> {
+        iq.iter()
+            .map(|&sample| {
+                let tone = Complex32::from_polar(1.0, self.phase);
+                self.phase = (self.phase + self.step) % (2.0 * PI);
+
+                let mark = sample * tone.conj();
+                let space = sample * tone;
+                self.mark_sum += mark;
+                self.space_sum += space;
+                self.mark.push_back(mark);
+                self.space.push_back(space);
+                if self.mark.len() > self.length {
+                    self.mark_sum -= self.mark.pop_front().unwrap_or_default();
+                    self.space_sum -= self.space.pop_front().unwrap_or_default();
+                }
+
+                // Recompute the running sums now and then to stop rounding errors from accumulating
+                self.since_refresh += 1;
+                if self.since_refresh >= constants::CORRELATOR_REFRESH_INTERVAL {
+                    self.since_refresh = 0;
+                    self.mark_sum = self.mark.iter().sum();
+                    self.space_sum = self.space.iter().sum();
+                }
+
+                let mark_energy = self.mark_sum.norm_sqr();
+                let space_energy = self.space_sum.norm_sqr();
+                let total = (mark_energy + space_energy).max(f32::MIN_POSITIVE);
+                self.deviation * (mark_energy - space_energy) / total
+            })
+            .collect()
+    }
+}
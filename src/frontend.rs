@@ -0,0 +1,161 @@
+use num_complex::Complex32;
+use crate::constants;
+
+/// Sample front end settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontEndConfig {
+    pub dc_removal: bool,    // subtract the tracked DC offset
+    pub iq_correction: bool, // rebalance the gain and phase of Q against I
+}
+
+impl Default for FrontEndConfig {
+    fn default() -> Self {
+        Self {
+            dc_removal: true,
+            iq_correction: true,
+        }
+    }
+}
+
+/// Imperfections of the dongle as currently estimated.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrontEndEstimates {
+    pub dc_offset: Complex32,    // mean of the raw samples
+    pub gain_imbalance_db: f32,  // power of Q relative to I
+    pub phase_error_deg: f32,    // deviation of Q from quadrature
+    pub image_rejection_db: f32, // image rejection the imbalance leaves without correction
+}
+
+/// Corrects the DC offset and IQ imbalance of the raw dongle samples. The DC offset is
+/// tracked by a one-pole IIR. The imbalance is estimated blindly from the second order
+/// statistics of I and Q, which a received signal with noise makes equal and uncorrelated,
+/// and undone by projecting the I component out of Q and rescaling Q to the power of I.
+pub struct FrontEnd {
+    config: FrontEndConfig,
+    dc: Complex32,
+    power_i: f32, // E[I²] after DC removal
+    power_q: f32, // E[Q²]
+    cross: f32,   // E[I·Q]
+    count: usize, // samples seen, for the start-up averaging
+}
+
+impl FrontEnd {
+    pub fn new(config: FrontEndConfig) -> Self {
+        Self {
+            config,
+            dc: Complex32::new(0.0, 0.0),
+            power_i: 0.0,
+            power_q: 0.0,
+            cross: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: FrontEndConfig) {
+        self.config = config;
+    }
+
+    /// Forgets the estimates, as when the dongle is retuned or its gain is changed, which moves
+    /// its DC offset and imbalance. They are averaged from scratch again.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    pub fn estimates(&self) -> FrontEndEstimates {
+        let gain = (self.power_q / self.power_i.max(f32::MIN_POSITIVE)).sqrt();
+        let correlation = self.cross / (self.power_i * self.power_q).sqrt().max(f32::MIN_POSITIVE);
+        let phase = correlation.clamp(-1.0, 1.0).asin();
+        let wanted = 1.0 + 2.0 * gain * phase.cos() + gain * gain;
+        let image = 1.0 - 2.0 * gain * phase.cos() + gain * gain;
+        FrontEndEstimates {
+            dc_offset: self.dc,
+            gain_imbalance_db: 20.0 * gain.max(f32::MIN_POSITIVE).log10(),
+            phase_error_deg: phase.to_degrees(),
+            image_rejection_db: 10.0 * (wanted / image.max(1e-12)).log10(),
+        }
+    }
+
+    /// Updates the estimates with a buffer and returns it corrected as configured. The estimates
+    /// are averaged over the samples seen so far until the trackers' own time constants take over.
+    pub fn process(&mut self, iq: &[Complex32]) -> Vec<Complex32> {
+        for &sample in iq {
+            self.count += 1;
+            let warm_up = 1.0 / self.count as f32;
+            let dc_gain = constants::FRONTEND_DC_ALPHA.max(warm_up);
+            let iq_gain = constants::FRONTEND_IQ_ALPHA.max(warm_up);
+
+            self.dc += (sample - self.dc) * dc_gain;
+            let centred = sample - self.dc;
+            self.power_i += iq_gain * (centred.re * centred.re - self.power_i);
+            self.power_q += iq_gain * (centred.im * centred.im - self.power_q);
+            self.cross += iq_gain * (centred.re * centred.im - self.cross);
+        }
+
+        // Q' = (Q - c·I)·s leaves Q' uncorrelated with I and as strong
+        let projection = self.cross / self.power_i.max(f32::MIN_POSITIVE);
+        let residual = self.power_q - projection * self.cross;
+        let scale = (self.power_i / residual.max(f32::MIN_POSITIVE)).sqrt();
+
+        let dc = if self.config.dc_removal { self.dc } else { Complex32::new(0.0, 0.0) };
+        iq.iter()
+            .map(|&sample| {
+                let sample = sample - dc;
+                if self.config.iq_correction && residual > 0.0 {
+                    Complex32::new(sample.re, (sample.im - projection * sample.re) * scale)
+                } else {
+                    sample
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noise with an offset and Q at `gain` times the level of I, `phase` radians off quadrature.
+    fn impaired(count: usize, dc: Complex32, gain: f32, phase: f32) -> Vec<Complex32> {
+        let mut state = 0x1234_5678u32;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        };
+        (0..count)
+            .map(|_| {
+                let (i, q) = (uniform(), uniform());
+                dc + Complex32::new(i, gain * (q * phase.cos() + i * phase.sin()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn estimates_and_corrects_imbalance() {
+        let mut front_end = FrontEnd::new(FrontEndConfig::default());
+        let corrected = front_end.process(&impaired(200_000, Complex32::new(0.05, -0.02), 1.1, 0.05));
+        let estimates = front_end.estimates();
+        assert!((estimates.dc_offset - Complex32::new(0.05, -0.02)).norm() < 0.005);
+        assert!((estimates.gain_imbalance_db - 20.0 * 1.1f32.log10()).abs() < 0.1);
+        assert!((estimates.phase_error_deg - 0.05f32.to_degrees()).abs() < 0.3);
+
+        let tail = &corrected[100_000..];
+        let power = |f: fn(&Complex32) -> f32| tail.iter().map(f).sum::<f32>() / tail.len() as f32;
+        let (power_i, power_q, cross) = (power(|s| s.re * s.re), power(|s| s.im * s.im), power(|s| s.re * s.im));
+        assert!((power_q / power_i - 1.0).abs() < 0.02);
+        assert!(cross.abs() / power_i < 0.02);
+    }
+
+    #[test]
+    fn reset_forgets_estimates() {
+        let mut front_end = FrontEnd::new(FrontEndConfig::default());
+        front_end.process(&impaired(50_000, Complex32::new(0.1, 0.1), 1.2, 0.1));
+        front_end.reset();
+        assert_eq!(front_end.estimates().dc_offset, Complex32::new(0.0, 0.0));
+        front_end.process(&impaired(50_000, Complex32::new(-0.05, 0.0), 1.0, 0.0));
+        let estimates = front_end.estimates();
+        assert!((estimates.dc_offset - Complex32::new(-0.05, 0.0)).norm() < 0.005);
+        assert!(estimates.gain_imbalance_db.abs() < 0.1);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::bitrate::BitRate;
+use crate::constants;
+
+/// HC-12 transparent transmission modes, selected with `AT+FUx`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hc12Mode {
+    /// Moderate power saving, fixed 250 kbps air rate at any serial baud rate.
+    Fu1,
+    /// Power saving, fixed 250 kbps air rate, serial baud rate limited to 4800.
+    Fu2,
+    /// Default mode, the air rate follows the serial baud rate.
+    Fu3,
+    /// Long range, 500 bps air rate at 1200 baud, short packets only.
+    Fu4,
+}
+
+impl Hc12Mode {
+    pub const ALL: [Hc12Mode; 4] = [Hc12Mode::Fu1, Hc12Mode::Fu2, Hc12Mode::Fu3, Hc12Mode::Fu4];
+
+    pub fn as_string(self) -> String {
+        match self {
+            Hc12Mode::Fu1 => "FU1".to_string(),
+            Hc12Mode::Fu2 => "FU2".to_string(),
+            Hc12Mode::Fu3 => "FU3".to_string(),
+            Hc12Mode::Fu4 => "FU4".to_string(),
+        }
+    }
+
+    /// Serial baud rates the module accepts in this mode.
+    pub fn serial_baud_rates(self) -> &'static [u32] {
+        let all = &constants::HC12_SERIAL_BAUD_RATES;
+        match self {
+            Hc12Mode::Fu1 | Hc12Mode::Fu3 => all,
+            Hc12Mode::Fu2 => &all[..3],
+            Hc12Mode::Fu4 => &all[..1],
+        }
+    }
+
+    /// Air data rate for a serial baud rate, as documented in the HC-12 datasheet.
+    pub fn air_rate(self, serial_baud: u32) -> BitRate {
+        match self {
+            Hc12Mode::Fu1 | Hc12Mode::Fu2 => BitRate::Rate250000,
+            Hc12Mode::Fu3 => match serial_baud {
+                0..=2400 => BitRate::Rate5000,
+                2401..=9600 => BitRate::Rate15000,
+                9601..=38400 => BitRate::Rate58000,
+                _ => BitRate::Rate236000,
+            },
+            Hc12Mode::Fu4 => BitRate::Rate500,
+        }
+    }
+
+    /// Largest payload the module sends in one packet. In FU4 longer serial input is split.
+    pub fn max_payload_len(self) -> usize {
+        match self {
+            Hc12Mode::Fu4 => constants::HC12_FU4_MAX_PAYLOAD_LENGTH,
+            _ => constants::HC12_MAX_PAYLOAD_LENGTH,
+        }
+    }
+}
+
+/// Centre frequency of channel `channel` (`AT+Cxxx`), 1 to `HC12_MAX_CHANNEL`.
+pub fn channel_frequency(channel: u8) -> u32 {
+    let channel = channel.clamp(1, constants::HC12_MAX_CHANNEL);
+    constants::HC12_CHANNEL_1_FREQUENCY + (channel as u32 - 1) * constants::HC12_CHANNEL_SPACING
+}
+
+/// Channel whose centre is exactly `frequency`, `None` for off-grid frequencies.
+pub fn channel_at(frequency: u32) -> Option<u8> {
+    let offset = frequency.checked_sub(constants::HC12_CHANNEL_1_FREQUENCY)?;
+    let channel = offset / constants::HC12_CHANNEL_SPACING + 1;
+    (offset % constants::HC12_CHANNEL_SPACING == 0 && channel <= constants::HC12_MAX_CHANNEL as u32)
+        .then_some(channel as u8)
+}
+
+/// Channel with the centre closest to `frequency`.
+pub fn nearest_channel(frequency: u32) -> u8 {
+    let offset = frequency.saturating_sub(constants::HC12_CHANNEL_1_FREQUENCY) + constants::HC12_CHANNEL_SPACING / 2;
+    (offset / constants::HC12_CHANNEL_SPACING + 1).min(constants::HC12_MAX_CHANNEL as u32) as u8
+}
+
+/// `count` adjacent channels including `channel`, starting with it unless that runs past the last channel.
+pub fn channel_block(channel: u8, count: usize) -> Vec<u8> {
+    let count = count.clamp(1, constants::HC12_MAX_CHANNEL as usize) as u8;
+    let first = channel.clamp(1, constants::HC12_MAX_CHANNEL - count + 1);
+    (first..first + count).collect()
+}
+
+/// Formats a channel the way the AT command names it, e.g. `CH021`.
+pub fn channel_name(channel: u8) -> String {
+    format!("CH{:03}", channel)
+}
+
+/// Radio settings as configured on the module: mode plus serial baud rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hc12Config {
+    pub mode: Hc12Mode,
+    pub serial_baud: u32,
+}
+
+impl Default for Hc12Config {
+    fn default() -> Self {
+        Self {
+            mode: Hc12Mode::Fu3,
+            serial_baud: constants::HC12_DEFAULT_SERIAL_BAUD,
+        }
+    }
+}
+
+impl Hc12Config {
+    /// Mode and the lowest baud rate that produce `rate` on air, keeping the current
+    /// setting if it already does. The serial baud rate itself cannot be seen on air.
+    pub fn for_air_rate(&self, rate: BitRate) -> Self {
+        if self.air_rate() == rate {
+            return *self;
+        }
+        let mode = match rate {
+            BitRate::Rate500 => Hc12Mode::Fu4,
+            BitRate::Rate250000 => Hc12Mode::Fu1,
+            _ => Hc12Mode::Fu3,
+        };
+        let serial_baud = mode.serial_baud_rates().iter()
+            .copied()
+            .find(|&baud| mode.air_rate(baud) == rate)
+            .unwrap_or(constants::HC12_DEFAULT_SERIAL_BAUD);
+        Self { mode, serial_baud }
+    }
+
+    /// Falls back to the nearest supported baud rate after the mode has changed.
+    pub fn with_supported_baud(&self) -> Self {
+        let rates = self.mode.serial_baud_rates();
+        let serial_baud = rates.iter()
+            .copied()
+            .min_by_key(|&baud| baud.abs_diff(self.serial_baud))
+            .unwrap_or(constants::HC12_DEFAULT_SERIAL_BAUD);
+        Self { mode: self.mode, serial_baud }
+    }
+
+    pub fn air_rate(&self) -> BitRate {
+        self.mode.air_rate(self.serial_baud)
+    }
+
+    pub fn deviation(&self) -> f32 {
+        self.air_rate().deviation()
+    }
+
+    pub fn max_payload_len(&self) -> usize {
+        self.mode.max_payload_len()
+    }
+
+    pub fn as_string(&self) -> String {
+        format!("{} @ {} baud", self.mode.as_string(), self.serial_baud)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_frequencies() {
+        assert_eq!(channel_frequency(1), 433_400_000);
+        assert_eq!(channel_frequency(2), 433_800_000);
+        assert_eq!(channel_frequency(100), 473_000_000);
+        assert_eq!(channel_frequency(127), 483_800_000);
+        // Out of range channel numbers are clamped
+        assert_eq!(channel_frequency(0), 433_400_000);
+        assert_eq!(channel_frequency(200), 483_800_000);
+    }
+
+    #[test]
+    fn channel_at_frequency() {
+        assert_eq!(channel_at(433_400_000), Some(1));
+        assert_eq!(channel_at(483_800_000), Some(127));
+        assert_eq!(channel_at(433_500_000), None);
+        assert_eq!(channel_at(433_000_000), None);
+        assert_eq!(channel_at(484_200_000), None);
+    }
+
+    #[test]
+    fn nearest_channel_to_frequency() {
+        assert_eq!(nearest_channel(433_400_000), 1);
+        assert_eq!(nearest_channel(430_000_000), 1);
+        assert_eq!(nearest_channel(433_599_999), 1);
+        assert_eq!(nearest_channel(433_600_000), 2);
+        assert_eq!(nearest_channel(483_800_000), 127);
+        assert_eq!(nearest_channel(490_000_000), 127);
+    }
+
+    #[test]
+    fn channel_blocks() {
+        assert_eq!(channel_block(1, 6), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(channel_block(50, 3), vec![50, 51, 52]);
+        assert_eq!(channel_block(127, 6), vec![122, 123, 124, 125, 126, 127]);
+        assert_eq!(channel_block(125, 6), vec![122, 123, 124, 125, 126, 127]);
+        assert_eq!(channel_block(127, 1), vec![127]);
+        assert_eq!(channel_block(1, 0), vec![1]);
+    }
+
+    #[test]
+    fn air_rates() {
+        for &baud in Hc12Mode::Fu1.serial_baud_rates() {
+            assert_eq!(Hc12Mode::Fu1.air_rate(baud), BitRate::Rate250000);
+        }
+        assert_eq!(Hc12Mode::Fu2.serial_baud_rates(), &[1200, 2400, 4800]);
+        for &baud in Hc12Mode::Fu2.serial_baud_rates() {
+            assert_eq!(Hc12Mode::Fu2.air_rate(baud), BitRate::Rate250000);
+        }
+        let fu3: Vec<BitRate> = Hc12Mode::Fu3.serial_baud_rates().iter().map(|&baud| Hc12Mode::Fu3.air_rate(baud)).collect();
+        assert_eq!(fu3, [BitRate::Rate5000, BitRate::Rate5000,
+                         BitRate::Rate15000, BitRate::Rate15000,
+                         BitRate::Rate58000, BitRate::Rate58000,
+                         BitRate::Rate236000, BitRate::Rate236000]);
+        assert_eq!(Hc12Mode::Fu4.serial_baud_rates(), &[1200]);
+        assert_eq!(Hc12Mode::Fu4.air_rate(1200), BitRate::Rate500);
+        assert_eq!(Hc12Config::default().air_rate(), BitRate::Rate15000);
+    }
+
+    #[test]
+    fn config_for_air_rate() {
+        let config = Hc12Config::default();
+        assert_eq!(config.for_air_rate(BitRate::Rate15000), config);
+        assert_eq!(config.for_air_rate(BitRate::Rate500), Hc12Config { mode: Hc12Mode::Fu4, serial_baud: 1200 });
+        assert_eq!(config.for_air_rate(BitRate::Rate250000), Hc12Config { mode: Hc12Mode::Fu1, serial_baud: 1200 });
+        assert_eq!(config.for_air_rate(BitRate::Rate58000), Hc12Config { mode: Hc12Mode::Fu3, serial_baud: 19200 });
+    }
+}
@@ -1,140 +1,329 @@
-
-
 use std::f32::consts::PI;
+use std::sync::Arc;
 use num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use crate::afc::Afc;
+use crate::constants;
+use crate::hc12::Hc12Config;
+use crate::detector::{gaussian_pulse, DemodConfig, Detector, QuadratureCorrelator};
+use crate::packet::{Packet, PacketConfig, PacketFramer, PacketQuality};
+use crate::power::PowerMeter;
+use crate::resampler::Resampler;
+use crate::slicer::Slicer;
+use crate::timing::{Symbol, SymbolSync};
 
 pub struct HC12Decoder {
-    center_frequency: f32,
     sample_rate: f32,
     freq_deviation: f32,      // Expected frequency deviation (Hz)
-    symbol_rate: f32,          // Symbol rate (baud)
-    samples_per_symbol: usize,
+    samples_per_symbol: f32,
     pub instant_freq: Vec<f32>,     // Instantaneous frequency samples
     pub filtered_freq: Vec<Complex32>,     // Filtered, instantaneous frequency samples
+    pub symbols: Vec<f32>,          // Recovered symbol values
+    pub timing_error: Vec<f32>,     // Timing error detector output, one value per symbol
+    last_sample: Complex32,         // Last IQ sample of the previous buffer
+    stream_start: usize,            // Stream index of the first sample since the stages were restarted
+    next_sample: usize,             // Stream index of the sample following the previous buffer
+    afc: Afc,                       // Coarse carrier offset removed ahead of the filter
+    slicer: Slicer,
+    power_meter: PowerMeter,
+    rssi_calibration_db: f32,       // dBFS to approximate dBm
     filter: Box<LowPassFilter>,
+    demod_config: DemodConfig,
+    matched_filter: Option<LowPassFilter>,
+    correlator: QuadratureCorrelator,
+    resampler: Resampler,
+    symbol_sync: SymbolSync,
+    framer: PacketFramer,
 }
 
 impl HC12Decoder {
-    pub fn new(center_frequency: f32, sample_rate: f32, symbol_rate: f32, freq_deviation: f32) -> Self {
+    pub fn new(sample_rate: f32, symbol_rate: f32, freq_deviation: f32) -> Self {
+        let samples_per_symbol = sample_rate / symbol_rate;
+        let resampled_rate = symbol_rate * constants::RESAMPLED_SAMPLES_PER_SYMBOL as f32;
         Self {
-            center_frequency,
             sample_rate,
             freq_deviation,
-            symbol_rate,
-            samples_per_symbol: (sample_rate / symbol_rate) as usize,
+            samples_per_symbol,
             instant_freq: Vec::new(),
             filtered_freq: Vec::new(),
-            filter: Box::new(LowPassFilter {
-                sample_rate: sample_rate,
-                cutoff_hz:   freq_deviation,
-                num_taps:    259,
-            })
+            filter: Box::new(LowPassFilter::new(sample_rate, freq_deviation + symbol_rate / 2.0 + constants::AFC_PULL_IN_HZ, constants::LOWPASS_NUM_TAPS)),
+            symbols: Vec::new(),
+            timing_error: Vec::new(),
+            last_sample: Complex32::new(0.0, 0.0),
+            stream_start: 0,
+            next_sample: 0,
+            afc: Afc::new(sample_rate),
+            slicer: Slicer::new(constants::HC12_PREAMBLE_MIN_BITS),
+            power_meter: PowerMeter::new(),
+            rssi_calibration_db: constants::RSSI_CALIBRATION_DB,
+            demod_config: DemodConfig::default(),
+            matched_filter: Some(LowPassFilter::from_kernel(gaussian_pulse(samples_per_symbol, constants::HC12_GAUSSIAN_BT))),
+            correlator: QuadratureCorrelator::new(sample_rate, samples_per_symbol, freq_deviation),
+            resampler: Resampler::new(sample_rate.round() as u32, resampled_rate.round() as u32),
+            symbol_sync: SymbolSync::new(constants::RESAMPLED_SAMPLES_PER_SYMBOL as f32,
+                                         constants::TIMING_LOOP_BANDWIDTH,
+                                         constants::TIMING_LOOP_DAMPING),
+            framer: PacketFramer::new(PacketConfig::default()),
+        }
+    }
+
+    /// Decoder for the air rate of an HC-12 mode and serial baud rate, with the deviation measured
+    /// on air if known and otherwise the nominal deviation of the rate.
+    pub fn for_mode(sample_rate: f32, config: &Hc12Config, deviation: Option<f32>) -> Self {
+        Self::new(sample_rate, config.air_rate().as_value() as f32, deviation.unwrap_or(config.deviation()))
+    }
+
+    /// Frequency deviation the decoder is set up for (Hz).
+    pub fn freq_deviation(&self) -> f32 {
+        self.freq_deviation
+    }
+
+    pub fn set_packet_config(&mut self, config: PacketConfig) {
+        self.slicer = Slicer::new(config.preamble_min_bits);
+        self.framer = PacketFramer::new(config);
+    }
+
+    pub fn set_demod_config(&mut self, config: DemodConfig) {
+        self.matched_filter = if config.matched_filter {
+            Some(LowPassFilter::from_kernel(gaussian_pulse(self.samples_per_symbol, config.gaussian_bt)))
+        } else {
+            None
+        };
+        self.correlator = QuadratureCorrelator::new(self.sample_rate, self.samples_per_symbol, self.freq_deviation);
+        self.demod_config = config;
+    }
+
+    /// Sets the offset added to dBFS levels to approximate dBm.
+    pub fn set_rssi_calibration(&mut self, calibration_db: f32) {
+        self.rssi_calibration_db = calibration_db;
+    }
+
+    /// Sets the noise floor of the unfiltered input (dBFS), as tracked by the squelch, or `None`
+    /// to estimate it from the in-channel power history. White noise is scaled by the channel filter.
+    pub fn set_input_noise_floor(&mut self, noise_floor_dbfs: Option<f32>) {
+        let noise_gain = self.filter.noise_gain();
+        self.power_meter.set_noise_reference(noise_floor_dbfs.map(|db| 10.0f32.powf(db / 10.0) * noise_gain));
+    }
+
+    /// Current in-channel noise floor (dBFS).
+    pub fn noise_floor_dbfs(&self) -> f32 {
+        self.power_meter.noise_floor_dbfs()
+    }
+
+    /// Total carrier offset currently removed by the AFC (Hz). The correlator output is
+    /// not a frequency, so with that detector only the coarse mixer offset is known.
+    pub fn frequency_offset(&self) -> f32 {
+        match self.demod_config.detector {
+            Detector::Discriminator => self.afc.offset() + self.slicer.threshold(),
+            Detector::QuadratureCorrelator => self.afc.offset(),
         }
     }
 
-    pub fn demodulate(&mut self, iq_samples: &[Complex32]) -> Result<Vec<f32>, String> {
+    /// Stream index of the first sample in `instant_freq`.
+    pub fn instant_freq_start(&self) -> usize {
+        self.next_sample - self.instant_freq.len()
+    }
+
+    /// Demodulates samples starting at stream index `start_sample`, such as the bursts passed on
+    /// by the squelch. Buffers that follow on from the previous one continue the stream, so
+    /// packets spanning a buffer boundary are reassembled. After a gap the stages are restarted,
+    /// the coarse mixer and the power history are kept.
+    pub fn demodulate_at(&mut self, start_sample: usize, iq_samples: &[Complex32]) -> Result<Vec<Packet>, String> {
 
         if iq_samples.is_empty() {
             return Err("No samples provided".to_string());
         }
+        if start_sample != self.next_sample {
+            self.restart(start_sample);
+        }
+        self.next_sample = start_sample + iq_samples.len();
 
-        // Stage 1: Low-pass filter to remove noise
-        self.filtered_freq = self.filter.lowpass_filter(iq_samples);
+        // Stage 1: Remove the coarse carrier offset and low-pass filter to remove noise
+        let mixed = self.afc.mix_down(iq_samples);
+        let filtered = self.filter.lowpass_filter(&mixed);
+        self.power_meter.process(&filtered);
 
         // Stage 2: Extract instantaneous frequency
-        self.instant_freq =self.compute_instantaneous_frequency(&self.filtered_freq);
+        self.instant_freq = self.compute_instantaneous_frequency(&filtered);
+        self.filtered_freq = filtered;
+
+        // Stage 3: Detection, matched filter or two-tone correlator
+        let detected = match self.demod_config.detector {
+            Detector::Discriminator => match self.matched_filter {
+                Some(ref mut matched_filter) => matched_filter.filter_real(&self.instant_freq),
+                None => self.instant_freq.clone(),
+            },
+            Detector::QuadratureCorrelator => self.correlator.process(&self.filtered_freq),
+        };
+
+        // Stage 4: Symbol timing recovery & decision
+        let symbols = self.recover_symbols(&detected);
+        self.symbols = symbols.iter().map(|s| s.value).collect();
+        self.timing_error = symbols.iter().map(|s| s.timing_error).collect();
 
-        // Stage 3: Symbol timing recovery & decision
-        let symbols = self.recover_symbols(&self.instant_freq);
+        // Stage 5: Slice symbols into soft bits against the tracked levels and frame them into packets.
+        let mut packets = Vec::new();
+        for symbol in &symbols {
+            let soft = self.slicer.decide(symbol.value, self.framer.is_searching());
+            let sample_index = self.stream_start + self.resampler.input_index(symbol.sample_index).saturating_sub(self.detector_delay());
+            if let Some(mut packet) = self.framer.push(soft, sample_index) {
+                packet.freq_offset = self.frequency_offset();
+                packet.quality = PacketQuality::from_soft_bits(&packet.soft_bits, self.slicer.noise());
+                // The power meter sees the filter output, which lags the input by the filter delay
+                packet.signal = self.power_meter.signal_strength(packet.start_sample + self.filter.delay(),
+                                                                 packet.end_sample + self.filter.delay(),
+                                                                 self.rssi_calibration_db);
+                self.update_nco();
+                packets.push(packet);
+            }
+        }
 
-        Ok(symbols)
+        Ok(packets)
     }
-    fn compute_instantaneous_frequency(&self, iq: &[Complex32]) -> Vec<f32> {
-        let num_samples = iq.len();
-        let mut freq = Vec::with_capacity(num_samples - 1);
 
-        // Calculate instantaneous frequency and calculate the arithmetic mean.
-        let mut mean = 0.0_f64;
-        for window in iq.windows(2) {
+    /// Clears the filter histories, timing loop and framer so a new stream starts at `start_sample`.
+    fn restart(&mut self, start_sample: usize) {
+        self.filter.reset();
+        if let Some(ref mut matched_filter) = self.matched_filter {
+            matched_filter.reset();
+        }
+        self.correlator = QuadratureCorrelator::new(self.sample_rate, self.samples_per_symbol, self.freq_deviation);
+        self.resampler.reset();
+        self.symbol_sync = SymbolSync::new(constants::RESAMPLED_SAMPLES_PER_SYMBOL as f32,
+                                           constants::TIMING_LOOP_BANDWIDTH,
+                                           constants::TIMING_LOOP_DAMPING);
+        self.slicer.reset();
+        self.framer.reset();
+        self.last_sample = Complex32::new(0.0, 0.0);
+        // Output samples of the filter before its history has filled up are not measured
+        self.power_meter.restart(start_sample, self.filter.delay() * 2);
+        self.stream_start = start_sample;
+    }
+
+    /// Delay from the IQ input to the detector output in samples.
+    fn detector_delay(&self) -> usize {
+        let detector = match self.demod_config.detector {
+            Detector::Discriminator => self.matched_filter.as_ref().map_or(0, |f| f.delay()),
+            Detector::QuadratureCorrelator => self.correlator.delay(),
+        };
+        self.filter.delay() + detector
+    }
+
+    /// Feeds the offset the slicer measured over a packet back into the AFC. Any packet with
+    /// a matching sync word counts, a failed CRC is often caused by the offset itself.
+    fn update_nco(&mut self) {
+        if self.demod_config.detector == Detector::Discriminator {
+            self.afc.update(self.slicer.threshold());
+        }
+    }
+
+    /// Phase difference discriminator. The last sample of the previous buffer is kept,
+    /// so the output has one frequency value per input sample.
+    fn compute_instantaneous_frequency(&mut self, iq: &[Complex32]) -> Vec<f32> {
+        let mut freq = Vec::with_capacity(iq.len());
+
+        for &sample in iq {
             // Phase difference = angle between consecutive samples
-            let phase_diff = (window[1] * window[0].conj()).arg();
+            let phase_diff = (sample * self.last_sample.conj()).arg();
+            self.last_sample = sample;
 
             // Convert to frequency: Δφ * sample_rate / (2π)
             let instant_freq = phase_diff * self.sample_rate / (2.0 * std::f32::consts::PI);
-            mean += instant_freq as f64;
 
             freq.push(instant_freq);
         }
-        mean /= freq.len() as f64;
-
-        // Subtract the mean from each frequency value to remove DC offset
-        for value in freq.iter_mut() {
-            *value -= mean as f32;
-        }
         freq
     }
 
-    fn recover_symbols(&self, filtered_freq: &[f32]) -> Vec<f32> {
-        let mut symbols = Vec::new();
+    /// Resamples the frequency to an integer number of samples per symbol, then tracks
+    /// the symbol centres with the Gardner loop and samples the frequency there.
+    fn recover_symbols(&mut self, detected: &[f32]) -> Vec<Symbol> {
+        let resampled = self.resampler.process(detected);
+        self.symbol_sync.process(&resampled)
+    }
+}
 
-        // Sample at symbol rate (take one sample per symbol period)
-        for chunk in filtered_freq.chunks(self.samples_per_symbol) {
-            if chunk.is_empty() { continue; }
+/// Streaming FIR filter, by default a Hamming-windowed sinc low-pass. The kernel is built once
+/// and the last `num_taps - 1` input samples are kept, so consecutive buffers filter seamlessly.
+pub struct LowPassFilter {
+    kernel: Vec<f32>,
+    history: Vec<Complex32>,          // tail of the previous input
+    overlap_save: Option<OverlapSave>, // FFT convolution for long kernels
+}
 
-            // Average over the symbol period
-            let symbol_value = chunk.iter().sum::<f32>() / chunk.len() as f32;
-            symbols.push(symbol_value);
+impl LowPassFilter {
+    pub fn new(sample_rate: f32, cutoff_hz: f32, num_taps: usize) -> Self {
+        Self::from_kernel(Self::build_kernel(sample_rate, cutoff_hz, num_taps))
+    }
+
+    /// Streaming filter with an arbitrary real kernel.
+    pub fn from_kernel(kernel: Vec<f32>) -> Self {
+        let overlap_save = if kernel.len() >= constants::FIR_FFT_MIN_TAPS {
+            Some(OverlapSave::new(&kernel))
+        } else {
+            None
+        };
+
+        Self {
+            history: vec![Complex32::new(0.0, 0.0); kernel.len() - 1],
+            kernel,
+            overlap_save,
         }
+    }
 
-        symbols
+    /// Group delay of the filter in samples.
+    pub fn delay(&self) -> usize {
+        self.kernel.len() / 2
     }
 
-    fn bits_to_bytes(bits: &[bool]) -> Vec<u8> {
-        bits.chunks(8).map(|chunk| {
-            chunk.iter().enumerate().fold(0u8, |acc, (i, &b)| {
-                acc | (if b { 1 << (7-i) } else { 0 })
-            })
-        }).collect()
+    /// Power gain for white noise, the sum of the squared coefficients.
+    pub fn noise_gain(&self) -> f32 {
+        self.kernel.iter().map(|h| h * h).sum()
     }
-}
 
-pub struct LowPassFilter {
-    pub sample_rate: f32,
-    pub cutoff_hz: f32,
-    pub num_taps: usize, // filter kernel length (odd recommended)
-}
+    /// Clears the history, as if the filter had only seen zeros so far.
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|v| *v = Complex32::new(0.0, 0.0));
+    }
 
-impl LowPassFilter {
-    /// Applies a Hamming-windowed sinc low-pass filter to IQ samples.
+    /// Applies the low-pass filter to IQ samples.
     /// Input  : time-domain IQ samples (Complex32: real=I, imag=Q)
-    /// Output : filtered IQ samples, same length as input
-    pub fn lowpass_filter(&self, iq_samples: &[Complex32]) -> Vec<Complex32> {
-        let kernel = self.build_kernel();
-        let half   = kernel.len() / 2;
-        let n      = iq_samples.len();
+    /// Output : filtered IQ samples, same length as input, delayed by `delay()` samples
+    pub fn lowpass_filter(&mut self, iq_samples: &[Complex32]) -> Vec<Complex32> {
+        let mut buffer = Vec::with_capacity(self.history.len() + iq_samples.len());
+        buffer.extend_from_slice(&self.history);
+        buffer.extend_from_slice(iq_samples);
 
-        (0..n)
-            .map(|i| {
-                kernel.iter().enumerate().fold(
-                    Complex32::new(0.0, 0.0),
-                    |acc, (j, &coeff)| {
-                        // center the kernel tap index
-                        let tap = i + j;
-                        if tap < half || tap - half >= n {
-                            acc // zero-pad out-of-bounds
-                        } else {
-                            acc + iq_samples[tap - half] * coeff
-                        }
-                    },
-                )
-            })
-            .collect()
+        let output = match self.overlap_save {
+            Some(ref mut overlap_save) => overlap_save.process(&buffer),
+            None => {
+                let taps = self.kernel.len();
+                (0..iq_samples.len())
+                    .map(|i| {
+                        self.kernel.iter().enumerate().fold(
+                            Complex32::new(0.0, 0.0),
+                            |acc, (j, &coeff)| acc + buffer[i + taps - 1 - j] * coeff,
+                        )
+                    })
+                    .collect()
+            }
+        };
+
+        let keep = self.history.len();
+        self.history.copy_from_slice(&buffer[buffer.len() - keep..]);
+        output
+    }
+
+    /// Filters a real valued stream, such as the discriminator output.
+    pub fn filter_real(&mut self, samples: &[f32]) -> Vec<f32> {
+        let complex: Vec<Complex32> = samples.iter().map(|&x| Complex32::new(x, 0.0)).collect();
+        self.lowpass_filter(&complex).iter().map(|c| c.re).collect()
     }
 
     /// Builds the normalized Hamming-windowed sinc kernel (real coefficients).
-    fn build_kernel(&self) -> Vec<f32> {
-        let cutoff_norm = self.cutoff_hz / self.sample_rate; // normalized [0.0, 0.5]
-        let m           = self.num_taps;
+    pub fn build_kernel(sample_rate: f32, cutoff_hz: f32, num_taps: usize) -> Vec<f32> {
+        let cutoff_norm = cutoff_hz / sample_rate; // normalized [0.0, 0.5]
+        let m           = num_taps;
         let half        = (m / 2) as f32;
 
         let mut h: Vec<f32> = (0..m)
@@ -162,3 +351,106 @@ impl LowPassFilter {
     }
 }
 
+
+/// Overlap-save FFT convolution with a fixed real kernel.
+struct OverlapSave {
+    taps: usize,
+    fft_size: usize,
+    kernel_spectrum: Vec<Complex32>, // kernel FFT, scaled by 1 / fft_size
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    block: Vec<Complex32>,
+}
+
+impl OverlapSave {
+    fn new(kernel: &[f32]) -> Self {
+        let taps = kernel.len();
+        let fft_size = (4 * taps).next_power_of_two();
+
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(fft_size);
+        let inverse = planner.plan_fft_inverse(fft_size);
+
+        let mut kernel_spectrum: Vec<Complex32> = kernel.iter()
+            .map(|&h| Complex32::new(h / fft_size as f32, 0.0))
+            .chain(std::iter::repeat(Complex32::new(0.0, 0.0)))
+            .take(fft_size)
+            .collect();
+        forward.process(&mut kernel_spectrum);
+
+        Self {
+            taps,
+            fft_size,
+            kernel_spectrum,
+            forward,
+            inverse,
+            block: vec![Complex32::new(0.0, 0.0); fft_size],
+        }
+    }
+
+    /// Filters `buffer`, whose first `taps - 1` samples are history.
+    /// Returns `buffer.len() - (taps - 1)` output samples.
+    fn process(&mut self, buffer: &[Complex32]) -> Vec<Complex32> {
+        let overlap = self.taps - 1;
+        let step = self.fft_size - overlap;
+        let n = buffer.len() - overlap;
+        let mut output = Vec::with_capacity(n);
+
+        let mut start = 0;
+        while start < n {
+            let len = step.min(n - start);
+            let segment = &buffer[start..start + overlap + len];
+            self.block[..segment.len()].copy_from_slice(segment);
+            self.block[segment.len()..].iter_mut().for_each(|v| *v = Complex32::new(0.0, 0.0));
+
+            self.forward.process(&mut self.block);
+            self.block.iter_mut()
+                .zip(self.kernel_spectrum.iter())
+                .for_each(|(x, h)| *x *= h);
+            self.inverse.process(&mut self.block);
+
+            // The first `overlap` outputs are circularly aliased and discarded
+            output.extend_from_slice(&self.block[overlap..overlap + len]);
+            start += len;
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlap_save_matches_direct_convolution() {
+        let kernel = LowPassFilter::build_kernel(64_000.0, 12_000.0, constants::LOWPASS_NUM_TAPS);
+        let input: Vec<Complex32> = (0..5000)
+            .map(|n| Complex32::new((n as f32 * 0.37).sin(), (n as f32 * 0.11).cos() - 0.5 * (n as f32 * 0.93).sin()))
+            .collect();
+        let direct: Vec<Complex32> = (0..input.len())
+            .map(|n| kernel.iter()
+                .enumerate()
+                .filter(|&(j, _)| j <= n)
+                .fold(Complex32::new(0.0, 0.0), |acc, (j, &h)| acc + input[n - j] * h))
+            .collect();
+
+        let mut filter = LowPassFilter::from_kernel(kernel);
+        assert!(filter.overlap_save.is_some());
+        // Buffers shorter and longer than one FFT segment, so outputs span segment and buffer boundaries
+        let mut output = Vec::new();
+        let mut rest = &input[..];
+        for size in [1, 100, 2000, 37, 766, 767].iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+            output.extend(filter.lowpass_filter(chunk));
+            rest = tail;
+        }
+
+        assert_eq!(output.len(), direct.len());
+        for (n, (a, b)) in output.iter().zip(&direct).enumerate() {
+            assert!((a - b).norm() < 1e-5, "sample {}: {} vs {}", n, a, b);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use num_complex::Complex32;
+use crate::constants;
+use crate::source::{convert_cu8, Playback, SampleBlock, SampleSource};
+
+/// Encoding of interleaved I and Q samples in a capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleFormat {
+    /// Unsigned 8 bit, as written by `rtl_sdr`.
+    Cu8,
+    /// Signed 8 bit, as written by HackRF tools.
+    Cs8,
+    /// Signed 16 bit little endian.
+    Cs16,
+    /// 32 bit float little endian, as written by GNU Radio and gqrx.
+    Cf32,
+}
+
+impl SampleFormat {
+    pub const ALL: [SampleFormat; 4] = [SampleFormat::Cu8, SampleFormat::Cs8, SampleFormat::Cs16, SampleFormat::Cf32];
+
+    pub fn as_string(self) -> String {
+        match self {
+            SampleFormat::Cu8 => "cu8".to_string(),
+            SampleFormat::Cs8 => "cs8".to_string(),
+            SampleFormat::Cs16 => "cs16".to_string(),
+            SampleFormat::Cf32 => "cf32".to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.as_string() == name.to_ascii_lowercase())
+    }
+
+    /// Format usually meant by a file extension.
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "cu8" | "u8" | "bin" => Some(SampleFormat::Cu8),
+            "cs8" | "s8" => Some(SampleFormat::Cs8),
+            "cs16" | "s16" => Some(SampleFormat::Cs16),
+            "cf32" | "fc32" | "cfile" => Some(SampleFormat::Cf32),
+            _ => None,
+        }
+    }
+
+    /// Bytes per complex sample.
+    pub fn sample_size(self) -> usize {
+        match self {
+            SampleFormat::Cu8 | SampleFormat::Cs8 => 2,
+            SampleFormat::Cs16 => 4,
+            SampleFormat::Cf32 => 8,
+        }
+    }
+
+    /// Converts a buffer of whole samples to complex values in the range -1.0 ... +1.0.
+    pub fn convert(self, buffer: &[u8]) -> Vec<Complex32> {
+        match self {
+            SampleFormat::Cu8 => convert_cu8(buffer),
+            SampleFormat::Cs8 => buffer.chunks_exact(2)
+                .map(|chunk| Complex32::new(chunk[0] as i8 as f32 / 128.0, chunk[1] as i8 as f32 / 128.0))
+                .collect(),
+            SampleFormat::Cs16 => buffer.chunks_exact(4)
+                .map(|chunk| Complex32::new(i16::from_le_bytes([chunk[0], chunk[1]]) as f32 / 32768.0,
+                                            i16::from_le_bytes([chunk[2], chunk[3]]) as f32 / 32768.0))
+                .collect(),
+            SampleFormat::Cf32 => buffer.chunks_exact(8)
+                .map(|chunk| Complex32::new(f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                                            f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]])))
+                .collect(),
+        }
+    }
+}
+
+/// What is known about a capture. Each field is taken from the command line if given there,
+/// otherwise from the SigMF metadata, the file name or the WAV header. A WAV file's sample
+/// format is always its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileSettings {
+    pub format: Option<SampleFormat>,
+    pub sample_rate: Option<u32>,
+    pub frequency: Option<u32>,         // centre frequency (Hz)
+    pub start_time: Option<SystemTime>, // capture time of the first sample
+}
+
+impl FileSettings {
+    /// Fields of `self`, with the missing ones taken from `other`.
+    pub fn or(self, other: FileSettings) -> Self {
+        Self {
+            format: self.format.or(other.format),
+            sample_rate: self.sample_rate.or(other.sample_rate),
+            frequency: self.frequency.or(other.frequency),
+            start_time: self.start_time.or(other.start_time),
+        }
+    }
+
+    /// Reads the settings from the name of a capture. Fields separated by `_` may carry a
+    /// frequency ending in `Hz` or a rate ending in `sps`, with an optional k, M or G prefix,
+    /// like SDR#'s `SDRSharp_20240101_120000Z_433400000Hz_IQ.wav`. gqrx names its captures
+    /// `gqrx_date_time_frequency_rate_fc.raw`, with cf32 samples.
+    pub fn from_file_name(path: &Path) -> Self {
+        let mut settings = Self {
+            format: path.extension().and_then(|e| e.to_str()).and_then(SampleFormat::from_extension),
+            ..Self::default()
+        };
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let fields: Vec<&str> = stem.split('_').collect();
+
+        if fields.len() == 6 && fields[0] == "gqrx" && fields[5] == "fc" {
+            settings.frequency = fields[3].parse().ok();
+            settings.sample_rate = fields[4].parse().ok();
+            settings.format = Some(SampleFormat::Cf32);
+            return settings;
+        }
+
+        for field in fields {
+            let field = field.to_ascii_lowercase();
+            if let Some(value) = field.strip_suffix("hz") {
+                settings.frequency = settings.frequency.or(parse_metric(value));
+            } else if let Some(value) = field.strip_suffix("sps") {
+                settings.sample_rate = settings.sample_rate.or(parse_metric(value));
+            }
+        }
+        settings
+    }
+}
+
+/// Parses a number with an optional k, M or G multiplier, such as `433.4M` or `2048k`. Zero is
+/// rejected, it is neither a frequency nor a sample rate.
+pub fn parse_metric(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let (number, multiplier) = match text.chars().last()? {
+        'k' | 'K' => (&text[..text.len() - 1], 1e3),
+        'm' | 'M' => (&text[..text.len() - 1], 1e6),
+        'g' | 'G' => (&text[..text.len() - 1], 1e9),
+        _ => (text, 1.0),
+    };
+    let value = number.parse::<f64>().ok()? * multiplier;
+    (0.5..=u32::MAX as f64).contains(&value).then_some(value.round() as u32)
+}
+
+/// Layout of the sample data in a file.
+struct DataLayout {
+    format: SampleFormat,
+    offset: u64, // position of the first sample
+    length: u64, // bytes of sample data
+}
+
+/// Reads the header of a 2 channel WAV file: I on the left channel, Q on the right, as 8 bit
+/// unsigned, 16 bit signed or 32 bit float samples. Returns the layout and the sample rate.
+fn read_wav_header(file: &mut File) -> Result<(DataLayout, u32), String> {
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).map_err(|e| format!("Failed to read WAV header: {}", e))?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err("Not a WAV file".to_string());
+    }
+
+    let mut format = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        file.read_exact(&mut chunk).map_err(|_| "WAV file has no data chunk".to_string())?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+        match &chunk[0..4] {
+            b"fmt " => {
+                if size < 16 {
+                    return Err("WAV format chunk too short".to_string());
+                }
+                // Nothing past the sub-format of WAVE_FORMAT_EXTENSIBLE is used, the rest is skipped
+                let mut fmt = vec![0u8; size.min(26) as usize];
+                file.read_exact(&mut fmt).map_err(|e| format!("Failed to read WAV format: {}", e))?;
+                let rest = size - fmt.len() as u64 + size % 2;
+                file.seek(SeekFrom::Current(rest as i64)).map_err(|e| e.to_string())?;
+                let mut tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                // WAVE_FORMAT_EXTENSIBLE keeps the actual format at the start of its sub-format GUID
+                if tag == 0xFFFE && fmt.len() >= 26 {
+                    tag = u16::from_le_bytes([fmt[24], fmt[25]]);
+                }
+                if sample_rate == 0 {
+                    return Err("WAV sample rate must be positive".to_string());
+                }
+                if channels != 2 {
+                    return Err(format!("WAV file has {} channels, IQ needs 2", channels));
+                }
+                format = Some((match (tag, bits) {
+                    (1, 8) => SampleFormat::Cu8,
+                    (1, 16) => SampleFormat::Cs16,
+                    (3, 32) => SampleFormat::Cf32,
+                    _ => return Err(format!("Unsupported WAV sample format {} with {} bits", tag, bits)),
+                }, sample_rate));
+            }
+            b"data" => {
+                let (format, sample_rate) = format.ok_or("WAV data before format chunk")?;
+                let offset = file.stream_position().map_err(|e| e.to_string())?;
+                let layout = DataLayout { format, offset, length: size };
+                return Ok((layout, sample_rate));
+            }
+            _ => {
+                file.seek(SeekFrom::Current((size + size % 2) as i64)).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+}
+
+/// Plays back a capture: raw interleaved IQ in one of the `SampleFormat`s, or a 2 channel WAV.
+/// Blocks are released at the recorded sample rate times the playback speed.
+pub struct IqFileSource {
+    path: PathBuf,
+    reader: BufReader<File>,
+    layout: DataLayout,
+    settings: FileSettings,
+    sample_rate: u32,
+    playback: Playback,
+    start_time: SystemTime, // capture time of the first sample
+    finished: bool,
+}
+
+impl IqFileSource {
+    /// Opens a capture. `overrides` replace what the file says about itself; without a
+    /// sample rate from either, `default_sample_rate` is assumed.
+    pub fn open(path: &Path, overrides: FileSettings, default_sample_rate: u32) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let metadata = file.metadata().map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+        let mut settings = overrides.or(FileSettings::from_file_name(path));
+        let is_wav = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+        let layout = if is_wav {
+            let (layout, sample_rate) = read_wav_header(&mut file)?;
+            settings.sample_rate = settings.sample_rate.or(Some(sample_rate));
+            // Recorders that stream the file may leave the data size unset
+            DataLayout { length: layout.length.min(metadata.len().saturating_sub(layout.offset)), ..layout }
+        } else {
+            DataLayout {
+                format: settings.format.unwrap_or(SampleFormat::Cu8),
+                offset: 0,
+                length: metadata.len(),
+            }
+        };
+        settings.format = Some(layout.format);
+        let sample_rate = settings.sample_rate.unwrap_or(default_sample_rate);
+        if sample_rate == 0 {
+            return Err(format!("Failed to open {}: sample rate must be positive", path.display()));
+        }
+        let length = (layout.length / layout.format.sample_size() as u64) as usize;
+
+        // Unless the capture time is known, the modification time is taken as when the capture ended
+        let duration = Duration::from_secs_f64(length as f64 / sample_rate as f64);
+        let start_time = settings.start_time
+            .or_else(|| metadata.modified().ok().and_then(|modified| modified.checked_sub(duration)))
+            .unwrap_or_else(SystemTime::now);
+
+        let mut source = Self {
+            path: path.to_path_buf(),
+            reader: BufReader::new(file),
+            layout,
+            settings,
+            sample_rate,
+            playback: Playback::new(sample_rate, length),
+            start_time,
+            finished: false,
+        };
+        source.seek_to(0)?;
+        Ok(source)
+    }
+
+    fn seek_to(&mut self, sample: usize) -> Result<(), String> {
+        let position = self.layout.offset + (sample * self.layout.format.sample_size()) as u64;
+        self.reader.seek(SeekFrom::Start(position))
+            .map_err(|e| format!("Failed to seek in {}: {}", self.path.display(), e))?;
+        self.finished = false;
+        Ok(())
+    }
+
+    /// Reads up to a buffer's worth of whole samples at the current position.
+    fn read_samples(&mut self) -> Vec<Complex32> {
+        let sample_size = self.layout.format.sample_size();
+        let remaining = self.playback.length().saturating_sub(self.playback.position());
+        let count = remaining.min(constants::SDR_BUFFER_SIZE / 2);
+        let mut buffer = Vec::with_capacity(count * sample_size);
+        if let Err(e) = self.reader.by_ref().take((count * sample_size) as u64).read_to_end(&mut buffer) {
+            eprintln!("Failed to read {}: {}", self.path.display(), e);
+        }
+        buffer.truncate(buffer.len() / sample_size * sample_size);
+        self.layout.format.convert(&buffer)
+    }
+}
+
+impl SampleSource for IqFileSource {
+    fn next_block(&mut self) -> Option<SampleBlock> {
+        if let Some(sample) = self.playback.take_seek() {
+            if let Err(e) = self.seek_to(sample) {
+                eprintln!("{}", e);
+            }
+        }
+        if self.finished || !self.playback.is_due() {
+            return None;
+        }
+
+        let mut samples = self.read_samples();
+        if samples.is_empty() && self.playback.is_looping() {
+            self.playback.seek(0);
+            self.playback.take_seek();
+            self.seek_to(0).ok()?;
+            samples = self.read_samples();
+        }
+        if samples.is_empty() {
+            self.finished = true;
+            return None;
+        }
+
+        let block = SampleBlock {
+            start_sample: self.playback.position(),
+            timestamp: self.start_time + self.playback.time(),
+            samples,
+        };
+        self.playback.release(block.samples.len());
+        Some(block)
+    }
+
+    fn set_frequency(&mut self, _frequency: u32) {}
+
+    fn set_sample_rate(&mut self, _rate: u32) {}
+
+    fn set_gain(&mut self, _gain: i32) {}
+
+    fn set_frequency_correction(&mut self, _ppm: i32) {}
+
+    /// False once playback has reached the end of a file that is not looped.
+    fn is_running(&self) -> bool {
+        !self.finished
+    }
+
+    fn serial(&self) -> Option<String> {
+        None
+    }
+
+    fn name(&self) -> String {
+        format!("File {} ({})",
+                self.path.file_name().map_or_else(|| self.path.display().to_string(), |name| name.to_string_lossy().to_string()),
+                self.layout.format.as_string())
+    }
+
+    fn recorded_sample_rate(&self) -> Option<u32> {
+        Some(self.sample_rate)
+    }
+
+    fn recorded_frequency(&self) -> Option<u32> {
+        self.settings.frequency
+    }
+
+    fn playback(&mut self) -> Option<&mut Playback> {
+        Some(&mut self.playback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2 channel WAV file with a format chunk of `fmt_size` bytes and an odd sized chunk
+    /// ahead of the data, as some recorders write them.
+    fn wav(tag: u16, bits: u16, sample_rate: u32, fmt_size: u32, data: &[u8]) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(2u16.to_le_bytes());
+        fmt.extend(sample_rate.to_le_bytes());
+        fmt.extend((sample_rate * bits as u32 / 4).to_le_bytes());
+        fmt.extend((bits / 4).to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        fmt.resize(fmt_size as usize, 0);
+
+        let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+        file.extend(b"fmt ");
+        file.extend(fmt_size.to_le_bytes());
+        file.extend(fmt);
+        file.extend(b"LIST\x03\0\0\0abc\0");
+        file.extend(b"data");
+        file.extend((data.len() as u32).to_le_bytes());
+        file.extend(data);
+        file
+    }
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hc12_iq_file_test_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn converts_formats() {
+        assert_eq!(SampleFormat::Cu8.convert(&[0, 255, 1]), vec![Complex32::new(-1.0, 1.0)]);
+        assert_eq!(SampleFormat::Cs8.convert(&[0x80, 0x40]), vec![Complex32::new(-1.0, 0.5)]);
+        assert_eq!(SampleFormat::Cs16.convert(&[0x00, 0x80, 0x00, 0x40]), vec![Complex32::new(-1.0, 0.5)]);
+        let cf32: Vec<u8> = [0.25f32, -0.75].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(SampleFormat::Cf32.convert(&cf32), vec![Complex32::new(0.25, -0.75)]);
+        for format in SampleFormat::ALL {
+            assert_eq!(SampleFormat::from_name(&format.as_string().to_uppercase()), Some(format));
+            assert_eq!(format.convert(&vec![0; format.sample_size() * 3 + 1]).len(), 3);
+        }
+    }
+
+    #[test]
+    fn parses_metric_values() {
+        assert_eq!(parse_metric("433.4M"), Some(433_400_000));
+        assert_eq!(parse_metric("2048k"), Some(2_048_000));
+        assert_eq!(parse_metric(" 1g "), Some(1_000_000_000));
+        assert_eq!(parse_metric("250000"), Some(250_000));
+        for text in ["", "0", "0k", "-5", "5G", "M", "abc"] {
+            assert_eq!(parse_metric(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn settings_from_file_name() {
+        let settings = FileSettings::from_file_name(Path::new("SDRSharp_20240101_120000Z_433400000Hz_IQ.wav"));
+        assert_eq!(settings.frequency, Some(433_400_000));
+        assert_eq!(settings.sample_rate, None);
+
+        let settings = FileSettings::from_file_name(Path::new("/captures/hc12_433.4MHz_2.048Msps.cs16"));
+        assert_eq!(settings.format, Some(SampleFormat::Cs16));
+        assert_eq!(settings.frequency, Some(433_400_000));
+        assert_eq!(settings.sample_rate, Some(2_048_000));
+
+        let settings = FileSettings::from_file_name(Path::new("gqrx_20240101_120000_433400000_1800000_fc.raw"));
+        assert_eq!(settings.format, Some(SampleFormat::Cf32));
+        assert_eq!(settings.frequency, Some(433_400_000));
+        assert_eq!(settings.sample_rate, Some(1_800_000));
+
+        let settings = FileSettings::from_file_name(Path::new("capture_0Hz.cu8"));
+        assert_eq!(settings.format, Some(SampleFormat::Cu8));
+        assert_eq!(settings.frequency, None);
+    }
+
+    #[test]
+    fn reads_wav() {
+        let data: Vec<u8> = [0i16, -32768, 16384, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let path = temp_file("reads.wav", &wav(1, 16, 250_000, 18, &data));
+        let mut source = IqFileSource::open(&path, FileSettings::default(), 2_048_000).unwrap();
+        assert_eq!(source.recorded_sample_rate(), Some(250_000));
+        assert_eq!(source.next_block().unwrap().samples, vec![Complex32::new(0.0, -1.0), Complex32::new(0.5, 0.0)]);
+        assert!(source.next_block().is_none());
+        assert!(!source.is_running());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn rejects_zero_sample_rate() {
+        let path = temp_file("zero.wav", &wav(3, 32, 0, 16, &[0; 8]));
+        assert!(IqFileSource::open(&path, FileSettings::default(), 2_048_000).is_err());
+        std::fs::remove_file(path).ok();
+
+        let path = temp_file("zero.cu8", &[128; 16]);
+        let overrides = FileSettings { sample_rate: Some(0), ..FileSettings::default() };
+        assert!(IqFileSource::open(&path, overrides, 2_048_000).is_err());
+        assert!(IqFileSource::open(&path, FileSettings::default(), 0).is_err());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn rejects_oversized_format_chunk() {
+        let mut file = wav(1, 8, 250_000, 16, &[128; 4]);
+        file[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        let path = temp_file("oversized.wav", &file);
+        assert!(IqFileSource::open(&path, FileSettings::default(), 2_048_000).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -1,44 +1,102 @@
 
+mod afc;
+mod bitrate;
+mod calibration;
+mod channelizer;
 mod constants;
+mod crc;
+mod decimator;
+mod detector;
+mod frontend;
+mod resampler;
 mod rtlsdr;
+mod slicer;
+mod squelch;
+mod timing;
+mod hc12;
 mod hc12_decoder;
+mod iq_file;
+mod packet;
+mod power;
+mod receiver;
+mod reference;
+mod rtl_tcp;
+mod sigmf;
+mod source;
+mod synthetic;
 mod visualizer;
+mod whitening;
 
 use eframe::egui;
 use egui::load::Result;
 use num_complex::Complex32;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
 use rtlsdr::RTLSDRController;
+use bitrate::{BitRate, BitRateDetector, BitRateEstimate};
+use calibration::{CalibrationWizard, PpmStore};
+use channelizer::Channelizer;
+use crc::{CrcBitOrder, CrcPolynomial, CrcStatus};
+use decimator::DecimationPlan;
+use detector::{DemodConfig, Detector};
+use frontend::{FrontEnd, FrontEndConfig};
+use hc12::{Hc12Config, Hc12Mode};
 use hc12_decoder::HC12Decoder;
-use visualizer::SignalVisualizer;
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum BitRate {
-    Rate5000,
-    Rate15000,
-    Rate58000,
-    Rate236000,
+use iq_file::{FileSettings, IqFileSource, SampleFormat};
+use packet::{Packet, PacketConfig};
+use receiver::ChannelReceiver;
+use reference::{ReferenceAnnotations, ReferenceStatus};
+use rtl_tcp::RtlTcpSource;
+use sigmf::{Annotation, RecordingInfo, SigmfMetadata, SigmfRecorder};
+use source::{SampleSource, SourceKind};
+use squelch::{Burst, BurstDetector, SquelchConfig};
+use synthetic::SyntheticSource;
+use visualizer::{SignalVisualizer, TimeMarker};
+
+const USAGE: &str = "Usage: HC12-RTLSDR-Demodulator [FILE] [--format cu8|cs8|cs16|cf32] [--rate HZ] [--frequency HZ]";
+
+/// Options given on the command line: a capture to open, and what to assume about it
+/// where the file does not say.
+struct CommandLine {
+    file: Option<PathBuf>,
+    file_settings: FileSettings,
 }
 
-impl BitRate {
-    fn as_value(self) -> u32 {
-        match self {
-            BitRate::Rate5000 => 5000,
-            BitRate::Rate15000 => 15000,
-            BitRate::Rate58000 => 58000,
-            BitRate::Rate236000 => 236000,
-        }
-    }
-    fn as_string(self) -> String {
-        match self {
-            BitRate::Rate5000 => "5000".to_string(),
-            BitRate::Rate15000 => "15000".to_string(),
-            BitRate::Rate58000 => "58000".to_string(),
-            BitRate::Rate236000 => "236000".to_string(),
+impl CommandLine {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut command_line = Self { file: None, file_settings: FileSettings::default() };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--format" => {
+                    let name = value()?;
+                    command_line.file_settings.format = Some(SampleFormat::from_name(&name).ok_or_else(|| format!("Unknown format {}", name))?);
+                }
+                "--rate" => {
+                    let rate = value()?;
+                    command_line.file_settings.sample_rate = Some(iq_file::parse_metric(&rate).ok_or_else(|| format!("Invalid sample rate {}", rate))?);
+                }
+                "--frequency" => {
+                    let frequency = value()?;
+                    command_line.file_settings.frequency = Some(iq_file::parse_metric(&frequency).ok_or_else(|| format!("Invalid frequency {}", frequency))?);
+                }
+                _ if !arg.starts_with("--") && command_line.file.is_none() => command_line.file = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
         }
+        Ok(command_line)
     }
 }
 
 fn main() -> Result<(), eframe::Error> {
+    let command_line = match CommandLine::parse(std::env::args().skip(1)) {
+        Ok(command_line) => command_line,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1300.0, 920.0])
@@ -49,133 +107,633 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "HC12 RTL-SDR Demodulator",
         options,
-        Box::new(|_cc| Ok(Box::new(HC12App::new()))),
+        Box::new(|_cc| Ok(Box::new(HC12App::new(command_line)))),
     )
 }
 
 struct HC12App {
-    rtlsdr: Option<RTLSDRController>,
-    decoder: HC12Decoder,
+    source: Option<Box<dyn SampleSource>>,
+    front_end: FrontEnd,
+    channelizer: Channelizer,
+    receivers: Vec<ChannelReceiver>, // one per received channel, the selected channel first
+    bitrate_detector: BitRateDetector,
     visualizer: SignalVisualizer,
     
     // Settings
+    source_kind: SourceKind,
+    source_address: String, // rtl_tcp server
+    source_path: String,    // IQ file
+    file_settings: FileSettings, // what to assume about the IQ file where it does not say
+    recording_path: String,      // SigMF recording, without extension
     frequency: u32,
+    tuning_offset: i32,
+    ppm: i32,
     gain: i32,
-    bit_rate: BitRate,
+    hc12: Hc12Config,
+    auto_bit_rate: bool,
     sample_rate: u32,
+    multi_channel: bool,
     bandwidth: u32,
+    packet_config: PacketConfig,
+    demod_config: DemodConfig,
+    squelch_config: SquelchConfig,
+    front_end_config: FrontEndConfig,
 
     // State
     current_samples: Vec<Complex32>,
-    decoded_symbols: Vec<f32>,
-    decoded_bytes: Vec<u8>,
-    decoded_text: String,
+    block_time: Option<SystemTime>,
+    next_sample: usize,     // stream index the next block should start at
+    receivers_start: usize, // stream index the receivers started at
+    recorder: Option<SigmfRecorder>,
+    recording_status: String, // outcome of the last recording, shown by the Record button
+    reference: Option<ReferenceAnnotations>, // annotations of the SigMF recording played back
+    packets: Vec<Packet>,
+    bursts: Vec<Burst>,
+    crc_ok_only: bool,
     status_message: String,
     is_running: bool,
+    ppm_store: PpmStore,
+    dongle_serial: Option<String>,
+    calibration: Option<CalibrationWizard>,
 }
 
 impl HC12App {
-    fn new() -> Self {
-        let rtlsdr = match RTLSDRController::new() {
-            Ok(controller) => {
-                println!("RTL-SDR initialized successfully");
-                Some(controller)
-            }
-            Err(e) => {
-                eprintln!("Failed to initialize RTL-SDR: {}", e);
-                eprintln!("Running in simulation mode");
-                None
-            }
-        };
-
-        let decoder = HC12Decoder::new(constants::SDR_DEFAULT_CENTER_FREQUENCY as f32,
-                                       constants::SDR_SAMPLE_RATE as f32,
-                                       BitRate::Rate15000.as_value() as f32,
-                                       15000.0);
-
-        Self {
-            rtlsdr,
-            decoder,
+    fn new(command_line: CommandLine) -> Self {
+        let mut app = Self {
+            source: None,
+            front_end: FrontEnd::new(FrontEndConfig::default()),
+            channelizer: Channelizer::new(constants::SDR_SAMPLE_RATE as f32, &[0.0], &DecimationPlan::NONE),
+            receivers: Vec::new(),
+            bitrate_detector: BitRateDetector::new(constants::SDR_SAMPLE_RATE as f32),
             visualizer: SignalVisualizer::new(),
 
+            source_kind: if command_line.file.is_some() { SourceKind::File } else { SourceKind::RtlSdr },
+            source_address: constants::RTL_TCP_DEFAULT_ADDRESS.to_string(),
+            source_path: command_line.file.map_or(String::new(), |path| path.display().to_string()),
+            file_settings: command_line.file_settings,
+            recording_path: String::new(),
             frequency: constants::SDR_DEFAULT_CENTER_FREQUENCY,
+            tuning_offset: constants::SDR_TUNING_OFFSET,
+            ppm: 0,
             gain: constants::SDR_DEFAULT_GAIN,
-            bit_rate: BitRate::Rate15000,
+            hc12: Hc12Config::default(),
+            auto_bit_rate: true,
             sample_rate: constants::SDR_SAMPLE_RATE,
+            multi_channel: false,
             bandwidth: 125_000,
+            packet_config: PacketConfig::default(),
+            demod_config: DemodConfig::default(),
+            squelch_config: SquelchConfig::default(),
+            front_end_config: FrontEndConfig::default(),
 
             current_samples: Vec::new(),
-            decoded_symbols: Vec::new(),
-            decoded_bytes: Vec::new(),
-            decoded_text: String::new(),
+            block_time: None,
+            next_sample: 0,
+            receivers_start: 0,
+            recorder: None,
+            recording_status: String::new(),
+            reference: None,
+            packets: Vec::new(),
+            bursts: Vec::new(),
+            crc_ok_only: false,
             status_message: String::from("Ready"),
             is_running: false,
+            ppm_store: PpmStore::load(constants::CALIBRATION_FILE),
+            dongle_serial: None,
+            calibration: None,
+        };
+        app.rebuild_receivers();
+        app.open_source();
+        app
+    }
+
+    /// Opens the selected source and brings it to the current sample rate, tuning, gain and
+    /// frequency correction. The stored correction is looked up once a dongle reports its serial number.
+    fn open_source(&mut self) {
+        self.stop_recording();
+        self.source = None;
+        self.reference = None;
+        let source: Result<Box<dyn SampleSource>, String> = match self.source_kind {
+            SourceKind::RtlSdr => RTLSDRController::new().map(|source| Box::new(source) as Box<dyn SampleSource>),
+            SourceKind::RtlTcp => RtlTcpSource::connect(&self.source_address).map(|source| Box::new(source) as Box<dyn SampleSource>),
+            SourceKind::File => self.open_file().map(|source| Box::new(source) as Box<dyn SampleSource>),
+            SourceKind::Synthetic => Ok(Box::new(SyntheticSource::new(self.receivers[0].frequency,
+                                                                      self.hc12,
+                                                                      self.packet_config.clone(),
+                                                                      self.sample_rate))),
+        };
+
+        match source {
+            Ok(source) => {
+                println!("Opened {}", source.name());
+                self.status_message = format!("Opened {}", source.name());
+                self.source = Some(source);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                self.status_message = e;
+            }
         }
+        self.dongle_serial = None;
+        self.block_time = None;
+        self.next_sample = 0;
+
+        // A recording brings its own rate, and only the channels within it can be received
+        self.sample_rate = self.source_sample_rate();
+        if let Some(center) = self.source.as_ref().and_then(|source| source.recorded_frequency()) {
+            if center.abs_diff(self.frequency) as f32 > self.sample_rate as f32 * constants::DECIMATION_PASSBAND {
+                self.frequency = hc12::channel_frequency(hc12::nearest_channel(center));
+            }
+        }
+        let tuned = self.tuned_frequency();
+        if let Some(ref mut source) = self.source {
+            source.set_sample_rate(self.sample_rate);
+            source.set_frequency(tuned);
+            source.set_gain(self.gain);
+            source.set_frequency_correction(self.ppm);
+        }
+        self.front_end.reset();
+        self.rebuild_receivers();
     }
-    
-    fn process_samples(&mut self) {
-        if let Some(ref rtlsdr) = self.rtlsdr {
-            if let Some(samples) = rtlsdr.get_samples() {
-                self.current_samples = samples.clone();
-                
-                // Decode HC12 signal
-                match self.decoder.demodulate(&samples) {
-                    Ok(result) => {
-                        self.decoded_symbols = result.clone();
-
-                        // Convert to bits
-                        let bits: Vec<bool> = self.decoded_symbols.iter().map(|&s| s > 0.0).collect();
-
-                        // 4. Pack bits into bytes
-                        let bytes:Vec<u8> = bits.chunks(8).map(|chunk| {
-                            chunk.iter().enumerate().fold(0u8, |acc, (i, &b)| {
-                                acc | (if b { 1 << (7-i) } else { 0 })
-                            })
-                        }).collect();
-
-                        let string: String = bytes.iter().map(|b| format!("{:02x} ", b)).collect();
-                        println!("{:#?}",string);
-
-                        self.status_message = format!(
-                            "Decoded {} symbols.",
-                            self.decoded_symbols.len(),
-                        );
-                    }
-                    Err(e) => {
-                        self.status_message = format!("Decode error: {}", e);
-                    }
+
+    /// Opens the capture at `source_path`. A SigMF recording, given by either of its files, is
+    /// read as its metadata says, and its annotations become the reference the decoded packets
+    /// are checked against. The channel they are on is selected.
+    fn open_file(&mut self) -> Result<IqFileSource, String> {
+        let path = Path::new(&self.source_path);
+        if !sigmf::is_sigmf(path) {
+            return IqFileSource::open(path, self.file_settings, self.sample_rate);
+        }
+        let metadata = SigmfMetadata::load(path)?;
+        let source = IqFileSource::open(&path.with_extension("sigmf-data"), self.file_settings.or(metadata.file_settings()), self.sample_rate)?;
+        println!("{} annotations in {}", metadata.annotations.len(), path.with_extension("sigmf-meta").display());
+        // Receive the channel of the first annotated packet
+        if let Some((lower, upper)) = metadata.annotations.iter().find_map(|a| a.freq_lower_edge.zip(a.freq_upper_edge)) {
+            self.frequency = hc12::channel_frequency(hc12::nearest_channel(((lower + upper) / 2.0) as u32));
+        }
+        self.reference = Some(ReferenceAnnotations::new(metadata.annotations));
+        Ok(source)
+    }
+
+    /// Sample rate to run at: a recording's own, otherwise the one the reception mode needs.
+    fn source_sample_rate(&self) -> u32 {
+        self.source.as_ref()
+            .and_then(|source| source.recorded_sample_rate())
+            .unwrap_or(if self.multi_channel { constants::CHANNELIZER_SAMPLE_RATE } else { constants::SDR_SAMPLE_RATE })
+    }
+
+    /// Mode a channel is decoded in: with automatic detection the one for the air rate last
+    /// detected on the channel, otherwise the selected one.
+    fn channel_config(&self, detected_rate: Option<BitRateEstimate>) -> Hc12Config {
+        match detected_rate {
+            Some(estimate) if self.auto_bit_rate => self.hc12.for_air_rate(estimate.rate),
+            _ => self.hc12,
+        }
+    }
+
+    /// Decoder for a channel with the current settings and the air rate detected on it. The
+    /// deviation measured on the last burst is used if that was sent at the decoded air rate.
+    fn new_decoder(&self, detected_rate: Option<BitRateEstimate>) -> HC12Decoder {
+        let hc12 = self.channel_config(detected_rate);
+        let deviation = detected_rate
+            .filter(|estimate| estimate.rate == hc12.air_rate())
+            .map(|estimate| estimate.deviation);
+        let mut decoder = HC12Decoder::for_mode(self.channel_sample_rate(), &hc12, deviation);
+        decoder.set_packet_config(PacketConfig { max_payload_len: hc12.max_payload_len(), ..self.packet_config.clone() });
+        decoder.set_demod_config(self.demod_config.clone());
+        decoder.set_rssi_calibration(self.rssi_calibration_db());
+        decoder
+    }
+
+    /// Recreates the decoders after a setting has changed. The squelches keep their noise floor.
+    fn rebuild_decoder(&mut self) {
+        let decoders: Vec<HC12Decoder> = self.receivers.iter().map(|r| self.new_decoder(r.detected_rate)).collect();
+        for (receiver, decoder) in self.receivers.iter_mut().zip(decoders) {
+            receiver.decoder = decoder;
+        }
+    }
+
+    /// Sets up the channels for the current frequency: the selected one, or with multi-channel
+    /// reception a block of adjacent channels around it. The channelizer mixes each of them
+    /// down from where it sits relative to the tuned frequency.
+    fn rebuild_receivers(&mut self) {
+        let channels: Vec<(Option<u8>, u32)> = if self.multi_channel {
+            hc12::channel_block(hc12::nearest_channel(self.frequency), constants::CHANNELIZER_CHANNELS)
+                .into_iter()
+                .map(|channel| (Some(channel), hc12::channel_frequency(channel)))
+                .collect()
+        } else {
+            vec![(hc12::channel_at(self.frequency), self.frequency)]
+        };
+
+        let tuned = self.tuned_frequency();
+        let offsets: Vec<f32> = channels.iter().map(|&(_, f)| f as f32 - tuned as f32).collect();
+        self.channelizer = Channelizer::new(self.sample_rate as f32, &offsets, &self.decimation_plan());
+        self.visualizer.set_tuning(tuned,
+                                   self.sample_rate,
+                                   channels.iter()
+                                       .map(|&(channel, f)| (channel.map_or("Channel".to_string(), hc12::channel_name), f))
+                                       .collect());
+
+        let rate = self.channel_sample_rate();
+        let mut receivers: Vec<ChannelReceiver> = channels.iter()
+            .map(|&(channel, frequency)| {
+                // A channel that is still received keeps the air rate detected on it
+                let detected_rate = self.receivers.iter().find(|r| r.frequency == frequency).and_then(|r| r.detected_rate);
+                ChannelReceiver::new(channel,
+                                     frequency,
+                                     BurstDetector::new(rate, self.squelch_config.clone()),
+                                     self.new_decoder(detected_rate),
+                                     detected_rate)
+            })
+            .collect();
+        // The selected channel first, it is the one shown in the plots
+        if let Some(selected) = receivers.iter().position(|r| r.frequency == hc12::channel_frequency(hc12::nearest_channel(self.frequency))) {
+            receivers[..=selected].rotate_right(1);
+        }
+        self.receivers = receivers;
+        self.receivers_start = self.next_sample;
+        self.bitrate_detector = BitRateDetector::new(rate);
+    }
+
+    /// Frequency the dongle is tuned to: the tuning offset away from the channel, or the middle
+    /// of the channel block, which already falls between two channels. A recording stays where
+    /// it was made.
+    fn tuned_frequency(&self) -> u32 {
+        if let Some(frequency) = self.source.as_ref().and_then(|source| source.recorded_frequency()) {
+            return frequency;
+        }
+        if !self.multi_channel {
+            return self.frequency.saturating_add_signed(self.tuning_offset);
+        }
+        let block = hc12::channel_block(hc12::nearest_channel(self.frequency), constants::CHANNELIZER_CHANNELS);
+        let first = hc12::channel_frequency(block[0]);
+        let last = hc12::channel_frequency(block[block.len() - 1]);
+        first + (last - first) / 2
+    }
+
+    /// Stream index of the source sample a channel sample was decimated from.
+    fn source_sample(&self, channel_sample: usize) -> usize {
+        self.receivers_start + channel_sample * self.decimation_plan().decimation()
+    }
+
+    /// Sample rate of the stream each decoder sees.
+    fn channel_sample_rate(&self) -> f32 {
+        self.decimation_plan().output_rate(self.sample_rate as f32)
+    }
+
+    /// Decimation from the device rate to the channel rate for the selected mode. With automatic
+    /// rate detection the channels stay wide enough for the fastest air rate, so any rate is seen.
+    fn decimation_plan(&self) -> DecimationPlan {
+        let rate = if self.auto_bit_rate { BitRate::Rate250000 } else { self.hc12.air_rate() };
+        DecimationPlan::for_air_rate(self.sample_rate as f32, rate)
+    }
+
+    /// Retunes the dongle, `frequency` in Hz.
+    fn set_frequency(&mut self, frequency: u32) {
+        self.frequency = frequency;
+        let tuned = self.tuned_frequency();
+        if let Some(ref mut source) = self.source {
+            source.set_frequency(tuned);
+        }
+        self.front_end.reset();
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.retune(tuned);
+        }
+        self.rebuild_receivers();
+    }
+
+    /// Switches between single channel reception and the channelizer, which needs the wide sample rate.
+    fn set_multi_channel(&mut self, multi_channel: bool) {
+        self.multi_channel = multi_channel;
+        let sample_rate = self.source_sample_rate();
+        // A SigMF recording has one sample rate throughout, it ends where the rate changes
+        if sample_rate != self.sample_rate && self.recorder.is_some() {
+            self.stop_recording();
+            self.recording_status = "Recording stopped, the sample rate changed".to_string();
+        }
+        self.sample_rate = sample_rate;
+        if let Some(ref mut source) = self.source {
+            source.set_sample_rate(self.sample_rate);
+        }
+        self.set_frequency(self.frequency);
+    }
+
+    /// Offset from dBFS to approximate dBm at the current tuner gain.
+    fn rssi_calibration_db(&self) -> f32 {
+        constants::RSSI_CALIBRATION_DB - self.gain as f32 / 10.0
+    }
+
+    /// Sets the crystal correction and remembers it for the dongle in use.
+    fn set_frequency_correction(&mut self, ppm: i32) {
+        self.ppm = ppm.clamp(-constants::SDR_MAX_PPM, constants::SDR_MAX_PPM);
+        if let Some(ref mut source) = self.source {
+            source.set_frequency_correction(self.ppm);
+        }
+        if let Some(ref serial) = self.dongle_serial {
+            if let Err(e) = self.ppm_store.set(serial, self.ppm) {
+                self.status_message = e;
+            }
+        }
+    }
+
+    /// Applies the stored correction once the dongle has been opened and reported its serial number.
+    fn load_frequency_correction(&mut self) {
+        if self.dongle_serial.is_some() {
+            return;
+        }
+        let Some(serial) = self.source.as_ref().and_then(|source| source.serial()) else {
+            return;
+        };
+        if let Some(ppm) = self.ppm_store.get(&serial) {
+            self.ppm = ppm;
+        }
+        if let Some(ref mut source) = self.source {
+            source.set_frequency_correction(self.ppm);
+        }
+        println!("Dongle {}: frequency correction {} ppm", serial, self.ppm);
+        self.dongle_serial = Some(serial);
+    }
+
+    /// Feeds the offsets of CRC-checked packets on the selected channel to the calibration wizard.
+    /// Only the discriminator measures the carrier offset, the correlator leaves it at zero.
+    fn measure_calibration(&mut self, packets: &[Packet]) {
+        if self.demod_config.detector != Detector::Discriminator {
+            return;
+        }
+        let Some(ref mut wizard) = self.calibration else {
+            return;
+        };
+        let channel = self.receivers[0].channel;
+        for packet in packets.iter().filter(|p| p.crc == CrcStatus::Ok && p.channel == channel) {
+            wizard.add_measurement(packet.freq_offset);
+        }
+    }
+
+    /// Decodes the next block of the source. Returns false if no block was due.
+    fn process_samples(&mut self) -> bool {
+        let Some(block) = self.source.as_mut().and_then(|source| source.next_block()) else {
+            // What the decoder has not reported by the end of a recording, it missed
+            if self.source.as_ref().is_some_and(|source| !source.is_running()) {
+                self.mark_missed_references(usize::MAX);
+            }
+            return false;
+        };
+        // A gap or jump in the stream would leave the channels mid-packet, start them afresh
+        if block.start_sample != self.next_sample {
+            eprintln!("Sample stream jumped from {} to {}", self.next_sample, block.start_sample);
+            self.next_sample = block.start_sample;
+            self.rebuild_receivers();
+            if let Some(ref mut reference) = self.reference {
+                reference.restart_at(block.start_sample);
+            }
+        }
+        self.next_sample += block.samples.len();
+        self.block_time = Some(block.timestamp);
+        if let Some(ref mut recorder) = self.recorder {
+            if let Err(e) = recorder.write(&block) {
+                self.stop_recording();
+                self.recording_status = e;
+            }
+        }
+        let samples = self.front_end.process(&block.samples);
+        self.current_samples = samples.clone();
+        
+        // Decode HC12 signal
+        let delay = (constants::REFERENCE_REPORT_DELAY_S * self.sample_rate as f32) as usize;
+        self.mark_missed_references(block.start_sample.saturating_sub(delay));
+        match self.decode(&samples) {
+            Ok(packets) => {
+                for packet in &packets {
+                    println!("Packet {} @{} ({} bytes, {}, {:+.0} Hz, RSSI {:.1} dBm, noise {:.1} dBm, SNR {:.1} dB, conf {:.2}, {} weak, BER {:.1e}{}): {}",
+                             packet.channel.map_or("-".to_string(), hc12::channel_name),
+                             packet.start_sample, packet.payload.len(), packet.crc.as_string(), packet.freq_offset,
+                             packet.signal.rssi_dbm(), packet.signal.noise_floor_dbm(), packet.signal.snr_db,
+                             packet.quality.mean_confidence, packet.quality.weak_bits, packet.quality.bit_error_probability,
+                             if packet.quality.is_marginal() { ", MARGINAL" } else { "" },
+                             packet.payload_hex());
+                }
+                self.measure_calibration(&packets);
+                let annotations = self.packet_annotations(&packets);
+                self.check_references(&annotations);
+                if let Some(ref mut recorder) = self.recorder {
+                    annotations.into_iter().for_each(|annotation| recorder.annotate(annotation));
                 }
+                self.log_packets(packets);
+
+                self.status_message = format!(
+                    "Decoded {} symbols, {} packets.",
+                    self.receivers[0].decoder.symbols.len(),
+                    self.packets.len(),
+                );
             }
+            Err(e) => {
+                self.status_message = format!("Decode error: {}", e);
+            }
+        }
+        true
+    }
+
+    /// Starts recording the source to `recording_path`, or to a name with the current time. An
+    /// existing recording is not overwritten.
+    fn start_recording(&mut self) {
+        let path = if self.recording_path.trim().is_empty() {
+            let time: String = sigmf::format_datetime(SystemTime::now())[..19].chars().filter(|c| c.is_ascii_digit() || *c == 'T').collect();
+            format!("hc12_{}Z", time)
         } else {
-            // Simulation mode - generate test data
-            self.current_samples = Self::generate_test_samples();
-            if let Ok(result) = self.decoder.demodulate(&self.current_samples) {
-                self.decoded_symbols = result;
+            self.recording_path.trim().to_string()
+        };
+        let info = RecordingInfo {
+            sample_rate: self.sample_rate,
+            frequency: self.tuned_frequency(),
+            gain: self.gain,
+            ppm: self.ppm,
+            serial: self.dongle_serial.clone(),
+            hardware: self.source.as_ref().map_or(String::new(), |source| source.name()),
+        };
+        match SigmfRecorder::create(Path::new(&path), info) {
+            Ok(recorder) => {
+                println!("Recording to {}", recorder.data_path().display());
+                self.recording_status.clear();
+                self.recorder = Some(recorder);
             }
+            Err(e) => self.recording_status = e,
         }
     }
-    
-    fn generate_test_samples() -> Vec<Complex32> {
-        use std::f32::consts::PI;
 
-        let mut samples = Vec::with_capacity(4096);
-        let t = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as f32 / 1000.0;
+    /// Ends the recording and writes its metadata.
+    fn stop_recording(&mut self) {
+        let Some(mut recorder) = self.recorder.take() else {
+            return;
+        };
+        self.recording_status = match recorder.finish() {
+            Ok(()) => format!("Recorded {:.1} s to {}",
+                              recorder.samples_written() as f32 / self.sample_rate as f32,
+                              recorder.meta_path().display()),
+            Err(e) => e,
+        };
+        println!("{}", self.recording_status);
+    }
 
-        for i in 0..4096 {
-            let phase = 2.0 * PI * (i as f32 / 4096.0 + t * 0.1);
-            let chirp = (i as f32 * 0.001 + t).sin() * 0.5;
-            let noise = (i as f32 * 12345.6789).sin() * 0.1;
+    /// Annotations of decoded packets, in the source's stream and with the band the transmitter occupied.
+    fn packet_annotations(&self, packets: &[Packet]) -> Vec<Annotation> {
+        packets.iter()
+            .filter_map(|packet| {
+                let receiver = self.receivers.iter().find(|r| r.channel == packet.channel)?;
+                let hc12 = self.channel_config(receiver.detected_rate);
+                let half_bandwidth = receiver.decoder.freq_deviation() as f64 + hc12.air_rate().as_value() as f64 / 2.0;
+                let frequency = receiver.frequency as f64 + packet.freq_offset as f64;
+                let sample_start = self.source_sample(packet.start_sample);
+                Some(Annotation {
+                    sample_start,
+                    sample_count: self.source_sample(packet.end_sample) - sample_start,
+                    freq_lower_edge: Some(frequency - half_bandwidth),
+                    freq_upper_edge: Some(frequency + half_bandwidth),
+                    label: format!("HC-12 {}", packet.channel.map_or("packet".to_string(), hc12::channel_name)),
+                    comment: packet.payload_text(),
+                    crc: Some(packet.crc),
+                    payload: Some(packet.payload.clone()),
+                })
+            })
+            .collect()
+    }
 
-            samples.push(Complex32::new(
-                (phase + chirp).cos() * 0.5 + noise,
-                (phase + chirp).sin() * 0.5 + noise,
-            ));
+    /// Checks decoded packets against the annotations of the recording played back.
+    fn check_references(&mut self, decoded: &[Annotation]) {
+        let Some(ref mut reference) = self.reference else {
+            return;
+        };
+        for annotation in decoded {
+            let Some(index) = reference.add_decoded(annotation) else {
+                println!("Reference: no annotation for packet @{} \"{}\"", annotation.sample_start, annotation.comment);
+                continue;
+            };
+            let expected = &reference.annotations()[index];
+            println!("Reference {} @{}: {}", expected.label, expected.sample_start, reference.status(index).as_string());
+            if reference.status(index) == ReferenceStatus::PayloadDiffers {
+                println!("  expected {}", expected.payload.as_deref().map(sigmf::format_hex).unwrap_or_default());
+                println!("  decoded  {}", annotation.payload.as_deref().map(sigmf::format_hex).unwrap_or_default());
+            }
         }
+    }
 
-        samples
+    /// Counts the annotations that end before `sample` and have not been decoded as missed.
+    fn mark_missed_references(&mut self, sample: usize) {
+        let Some(ref mut reference) = self.reference else {
+            return;
+        };
+        for index in reference.mark_missed(sample) {
+            let annotation = &reference.annotations()[index];
+            println!("Reference {} @{}: missed", annotation.label, annotation.sample_start);
+        }
+    }
+
+    /// Markers for the reference annotations on a plot of samples starting at stream index
+    /// `first_sample`, `spacing` stream samples apart. With a `frequency`, only the annotations
+    /// whose band covers it are marked.
+    fn reference_markers(&self, first_sample: usize, spacing: usize, frequency: Option<u32>) -> Vec<TimeMarker> {
+        let Some(ref reference) = self.reference else {
+            return Vec::new();
+        };
+        reference.annotations().iter()
+            .enumerate()
+            .filter(|(_, annotation)| match (frequency, annotation.freq_lower_edge, annotation.freq_upper_edge) {
+                (Some(frequency), Some(lower), Some(upper)) => (lower..=upper).contains(&(frequency as f64)),
+                _ => true,
+            })
+            .map(|(index, annotation)| TimeMarker {
+                start: (annotation.sample_start as f64 - first_sample as f64) / spacing as f64,
+                end: ((annotation.sample_start + annotation.sample_count) as f64 - first_sample as f64) / spacing as f64,
+                label: annotation.label.clone(),
+                color: match reference.status(index) {
+                    ReferenceStatus::Pending => egui::Color32::GRAY,
+                    ReferenceStatus::Matched => egui::Color32::from_rgb(0, 255, 0),
+                    ReferenceStatus::PayloadDiffers => egui::Color32::from_rgb(255, 180, 0),
+                    ReferenceStatus::Missed => egui::Color32::from_rgb(255, 60, 60),
+                    ReferenceStatus::Skipped => egui::Color32::DARK_GRAY,
+                },
+            })
+            .collect()
+    }
+
+    /// Mixes each received channel down from the buffer, then runs it through its squelch and decoder.
+    fn decode(&mut self, samples: &[Complex32]) -> Result<Vec<Packet>, String> {
+        let streams = self.channelizer.process(samples);
+        let mut packets = Vec::new();
+        let mut bursts = Vec::new();
+        for (receiver, stream) in self.receivers.iter_mut().zip(streams) {
+            let (channel_packets, channel_bursts) = receiver.process(&stream)?;
+            packets.extend(channel_packets);
+            bursts.extend(channel_bursts);
+        }
+
+        let rate = self.channel_sample_rate();
+        for burst in &bursts {
+            println!("Burst {} @{} ({:.1} ms, peak {:.1} dBFS, {:+.0} Hz)",
+                     burst.channel.map_or("-".to_string(), hc12::channel_name),
+                     burst.start_sample, burst.duration as f32 * 1000.0 / rate,
+                     burst.peak_power_dbfs, burst.freq_offset);
+        }
+        for burst in &bursts {
+            self.detect_bit_rate(burst);
+        }
+        self.log_bursts(bursts);
+        Ok(packets)
+    }
+
+    /// Classifies the air rate of a burst and, with automatic detection on, switches the decoder
+    /// of its channel to it. Each channel follows its own transmitters. A decoder at the right
+    /// rate is set up again if the measured deviation is well off the one it uses. The burst
+    /// itself has already been demodulated, the following ones use the new settings.
+    fn detect_bit_rate(&mut self, burst: &Burst) {
+        let Some(estimate) = self.bitrate_detector.estimate(&burst.samples) else {
+            return;
+        };
+        println!("Air rate {} {} bps (measured {:.0} bps, deviation {:.0} Hz, score {:.2})",
+                 burst.channel.map_or("-".to_string(), hc12::channel_name),
+                 estimate.rate.as_value(), estimate.measured_rate, estimate.deviation, estimate.score);
+        let Some(index) = self.receivers.iter().position(|r| r.channel == burst.channel) else {
+            return;
+        };
+        let previous = self.channel_config(self.receivers[index].detected_rate);
+        let hc12 = self.channel_config(Some(estimate));
+        let deviation = self.receivers[index].decoder.freq_deviation();
+        let deviation_off = estimate.rate == hc12.air_rate()
+            && (estimate.deviation - deviation).abs() > constants::BITRATE_DEVIATION_TOLERANCE * deviation;
+        self.receivers[index].detected_rate = Some(estimate);
+        if hc12 != previous || deviation_off {
+            self.receivers[index].decoder = self.new_decoder(Some(estimate));
+        }
+    }
+
+    /// Applies a new mode or baud rate, including the mode's packet length limit.
+    fn set_hc12_config(&mut self, config: Hc12Config) {
+        let plan = self.decimation_plan();
+        self.hc12 = config;
+        self.packet_config.max_payload_len = config.max_payload_len();
+        if self.decimation_plan() != plan {
+            self.rebuild_receivers();
+        } else {
+            self.rebuild_decoder();
+        }
+    }
+
+    /// Appends bursts to the activity log, dropping the oldest entries beyond `BURST_LOG_SIZE`.
+    fn log_bursts(&mut self, bursts: Vec<Burst>) {
+        self.bursts.extend(bursts);
+        if self.bursts.len() > constants::BURST_LOG_SIZE {
+            let excess = self.bursts.len() - constants::BURST_LOG_SIZE;
+            self.bursts.drain(..excess);
+        }
+    }
+
+    /// Appends packets to the log, dropping the oldest entries beyond `PACKET_LOG_SIZE`.
+    fn log_packets(&mut self, packets: Vec<Packet>) {
+        self.packets.extend(packets);
+        if self.packets.len() > constants::PACKET_LOG_SIZE {
+            let excess = self.packets.len() - constants::PACKET_LOG_SIZE;
+            self.packets.drain(..excess);
+        }
     }
 }
 
@@ -183,7 +741,10 @@ impl eframe::App for HC12App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Process samples if running
         if self.is_running {
-            self.process_samples();
+            self.load_frequency_correction();
+            // All blocks that are due, so playback faster than real time keeps its speed
+            let started = Instant::now();
+            while started.elapsed().as_secs_f32() < constants::PROCESSING_BUDGET_S && self.process_samples() {}
             ctx.request_repaint();
         }
         
@@ -197,6 +758,20 @@ impl eframe::App for HC12App {
                     self.is_running = !self.is_running;
                 }
                 
+                ui.separator();
+                ui.label(match hc12::channel_at(self.frequency) {
+                    Some(channel) => format!("{} ({:.3} MHz)", hc12::channel_name(channel), self.frequency as f64 / 1_000_000.0),
+                    None => format!("Off-grid ({:.3} MHz)", self.frequency as f64 / 1_000_000.0),
+                });
+                // The selected channel, as shown in the plots
+                let detected_rate = self.receivers[0].detected_rate;
+                ui.label(self.channel_config(detected_rate).as_string());
+                ui.label(match detected_rate {
+                    Some(estimate) => format!("Air rate: {} bps ({:.0} bps, ±{:.1} kHz)",
+                                              estimate.rate.as_string(), estimate.measured_rate, estimate.deviation / 1000.0),
+                    None => "Air rate: -".to_string(),
+                });
+
                 ui.separator();
                 ui.label(&self.status_message);
             });
@@ -206,19 +781,125 @@ impl eframe::App for HC12App {
         egui::SidePanel::left("settings").min_width(250.0).show(ctx, |ui| {
             ui.heading("Settings");
             ui.separator();
+
+            ui.label("Source:");
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("source_kind")
+                    .selected_text(self.source_kind.as_string())
+                    .show_ui(ui, |ui| {
+                        for kind in SourceKind::ALL {
+                            ui.selectable_value(&mut self.source_kind, kind, kind.as_string());
+                        }
+                    });
+                if ui.button("Open").clicked() {
+                    self.open_source();
+                }
+            });
+            match self.source_kind {
+                SourceKind::RtlTcp => {
+                    ui.text_edit_singleline(&mut self.source_address);
+                }
+                SourceKind::File => {
+                    ui.text_edit_singleline(&mut self.source_path);
+                }
+                SourceKind::RtlSdr | SourceKind::Synthetic => {}
+            }
+            ui.horizontal(|ui| {
+                if self.recorder.is_some() {
+                    if ui.button("⏹ Stop recording").clicked() {
+                        self.stop_recording();
+                    }
+                } else if ui.add_enabled(self.source.is_some(), egui::Button::new("⏺ Record")).clicked() {
+                    self.start_recording();
+                }
+                ui.add_enabled(self.recorder.is_none(), egui::TextEdit::singleline(&mut self.recording_path).hint_text("SigMF name"));
+            });
+            if let Some(ref recorder) = self.recorder {
+                ui.label(format!("Recorded {:.1} s, {} packets", recorder.samples_written() as f32 / self.sample_rate as f32, recorder.annotations()));
+            } else if !self.recording_status.is_empty() {
+                ui.label(&self.recording_status);
+            }
+            if let Some(playback) = self.source.as_mut().and_then(|source| source.playback()) {
+                ui.horizontal(|ui| {
+                    if ui.button(if playback.is_paused() { "▶ Play" } else { "⏸ Pause" }).clicked() {
+                        if playback.is_paused() {
+                            playback.play();
+                        } else {
+                            playback.pause();
+                        }
+                    }
+                    if ui.add_enabled(playback.is_paused(), egui::Button::new("⏭ Step")).clicked() {
+                        playback.step();
+                    }
+                    let mut looping = playback.is_looping();
+                    if ui.checkbox(&mut looping, "Loop").changed() {
+                        playback.set_looping(looping);
+                    }
+                });
+
+                let rate = self.sample_rate as f64;
+                let mut position = playback.position() as f64 / rate;
+                if ui.add(egui::Slider::new(&mut position, 0.0..=playback.length() as f64 / rate)
+                    .fixed_decimals(1)
+                    .suffix(" s")).changed() {
+                    playback.seek((position * rate) as usize);
+                }
+                let mut speed = playback.speed();
+                if ui.add(egui::Slider::new(&mut speed, 0.1..=10.0)
+                    .logarithmic(true)
+                    .fixed_decimals(1)
+                    .suffix("×")).changed() {
+                    playback.set_speed(speed);
+                }
+            }
+
+            ui.separator();
             
+            ui.label("Channel:");
+            let mut channel = hc12::channel_at(self.frequency);
+            egui::ComboBox::from_id_salt("hc12_channel")
+                .selected_text(channel.map_or("Off-grid".to_string(), hc12::channel_name))
+                .show_ui(ui, |ui| {
+                    for n in 1..=constants::HC12_MAX_CHANNEL {
+                        ui.selectable_value(&mut channel, Some(n), format!("{} ({:.1} MHz)",
+                                                                          hc12::channel_name(n),
+                                                                          hc12::channel_frequency(n) as f32 / 1_000_000.0));
+                    }
+                });
+            if let Some(n) = channel {
+                if hc12::channel_frequency(n) != self.frequency {
+                    self.set_frequency(hc12::channel_frequency(n));
+                }
+            }
+            let mut multi_channel = self.multi_channel;
+            if ui.checkbox(&mut multi_channel, format!("Receive {} channels", constants::CHANNELIZER_CHANNELS)).changed() {
+                self.set_multi_channel(multi_channel);
+            }
+
             ui.label("Frequency:");
-            let mut freq_mhz = self.frequency as f32 / 1_000_000.0;
-            if ui.add(egui::Slider::new(&mut freq_mhz,430.0..=470.0)
+            let mut freq_mhz = self.frequency as f64 / 1_000_000.0;
+            if ui.add(egui::DragValue::new(&mut freq_mhz)
+                .range(430.0..=490.0)
+                .speed(0.001)
                 .fixed_decimals(3)
-                .step_by(0.1)
                 .suffix(" MHz")).changed() {
-                self.frequency = (freq_mhz * 1_000_000.0) as u32;
-                if let Some(ref rtlsdr) = self.rtlsdr {
-                    rtlsdr.set_frequency(self.frequency);
-                    self.decoder = HC12Decoder::new(self.frequency as f32, self.sample_rate as f32, self.bit_rate.as_value() as f32, 15000.0);
-                }
+                self.set_frequency((freq_mhz * 1_000_000.0).round() as u32);
             }
+
+            let recorded = self.source.as_ref().is_some_and(|source| source.recorded_frequency().is_some());
+            ui.add_enabled_ui(!self.multi_channel && !recorded, |ui| {
+                ui.label("Tuning offset:");
+                let limit = self.sample_rate as f64 / 2_000.0;
+                let mut offset_khz = self.tuning_offset as f64 / 1000.0;
+                if ui.add(egui::DragValue::new(&mut offset_khz)
+                    .range(-limit..=limit)
+                    .speed(1.0)
+                    .fixed_decimals(0)
+                    .suffix(" kHz")).changed() {
+                    self.tuning_offset = (offset_khz * 1000.0).round() as i32;
+                    self.set_frequency(self.frequency);
+                }
+            });
             
             ui.separator();
 
@@ -229,28 +910,65 @@ impl eframe::App for HC12App {
                 .step_by(0.1)
                 .suffix(" dB")).changed() {
                 self.gain = (gain_db * 10.0) as i32;
-                if let Some(ref rtlsdr) = self.rtlsdr {
-                    rtlsdr.set_gain(self.gain);
+                self.front_end.reset();
+                let calibration_db = self.rssi_calibration_db();
+                self.receivers.iter_mut().for_each(|r| r.decoder.set_rssi_calibration(calibration_db));
+                if let Some(ref mut source) = self.source {
+                    source.set_gain(self.gain);
                 }
             }
 
+            ui.label("Frequency correction:");
+            ui.horizontal(|ui| {
+                let mut ppm = self.ppm;
+                if ui.add(egui::DragValue::new(&mut ppm)
+                    .range(-constants::SDR_MAX_PPM..=constants::SDR_MAX_PPM)
+                    .speed(0.2)
+                    .suffix(" ppm")).changed() {
+                    self.set_frequency_correction(ppm);
+                }
+                let measurable = self.demod_config.detector == Detector::Discriminator;
+                if ui.add_enabled(self.calibration.is_none() && measurable, egui::Button::new("Calibrate…"))
+                    .on_disabled_hover_text("Calibration measures the carrier offset with the discriminator detector")
+                    .clicked() {
+                    self.calibration = Some(CalibrationWizard::new(self.receivers[0].frequency, self.ppm));
+                }
+            });
+
             ui.separator();
 
-            ui.label("Bitrate:");
-            if ui.radio_value(&mut self.bit_rate, BitRate::Rate5000, BitRate::Rate5000.as_string()).clicked() {
-                self.bit_rate = BitRate::Rate5000;
-            }
-            if ui.radio_value(&mut self.bit_rate, BitRate::Rate15000, BitRate::Rate15000.as_string()).clicked() {
-                self.bit_rate = BitRate::Rate15000;
+            ui.label("HC-12 mode:");
+            let mut hc12 = self.channel_config(self.receivers[0].detected_rate);
+            if ui.checkbox(&mut self.auto_bit_rate, "Auto detect air rate").changed() {
+                // Selecting by hand starts from the mode detected on the selected channel
+                self.hc12 = hc12;
+                self.packet_config.max_payload_len = hc12.max_payload_len();
+                self.rebuild_receivers();
             }
-            if ui.radio_value(&mut self.bit_rate, BitRate::Rate58000, BitRate::Rate58000.as_string()).clicked() {
-                self.bit_rate = BitRate::Rate58000;
-            }
-            if ui.radio_value(&mut self.bit_rate, BitRate::Rate236000, BitRate::Rate236000.as_string()).clicked() {
-                self.bit_rate = BitRate::Rate236000;
+            ui.add_enabled_ui(!self.auto_bit_rate, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("hc12_mode")
+                        .selected_text(hc12.mode.as_string())
+                        .show_ui(ui, |ui| {
+                            for mode in Hc12Mode::ALL {
+                                ui.selectable_value(&mut hc12.mode, mode, mode.as_string());
+                            }
+                        });
+                    hc12 = hc12.with_supported_baud();
+                    egui::ComboBox::from_id_salt("hc12_baud")
+                        .selected_text(format!("{} baud", hc12.serial_baud))
+                        .show_ui(ui, |ui| {
+                            for &baud in hc12.mode.serial_baud_rates() {
+                                ui.selectable_value(&mut hc12.serial_baud, baud, format!("{} baud", baud));
+                            }
+                        });
+                });
+            });
+            ui.label(format!("Air rate: {} bps, ±{:.1} kHz", hc12.air_rate().as_string(), hc12.deviation() / 1000.0));
+            if !self.auto_bit_rate && hc12 != self.hc12 {
+                self.set_hc12_config(hc12);
             }
 
-
             ui.separator();
             
             ui.label("Bandwidth:");
@@ -259,31 +977,184 @@ impl eframe::App for HC12App {
                 .show_ui(ui, |ui| {
                     for bw in [125_000u32, 250_000, 500_000] {
                         if ui.selectable_value(&mut self.bandwidth, bw, format!("{} kHz", bw / 1000)).clicked() {
-                            self.decoder = HC12Decoder::new(self.frequency as f32, self.sample_rate as f32, self.bit_rate.as_value() as f32, 15000.0);
+                            self.rebuild_decoder();
                         }
                     }
                 });
+
+            ui.separator();
+
+            ui.label("Detector:");
+            let demod_before = self.demod_config.clone();
+            egui::ComboBox::from_id_salt("detector")
+                .selected_text(self.demod_config.detector.as_string())
+                .show_ui(ui, |ui| {
+                    for detector in Detector::ALL {
+                        ui.selectable_value(&mut self.demod_config.detector, detector, detector.as_string());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.demod_config.matched_filter, "Gaussian MF");
+                ui.label("BT:");
+                ui.add(egui::DragValue::new(&mut self.demod_config.gaussian_bt)
+                    .range(0.1..=1.0)
+                    .speed(0.05)
+                    .fixed_decimals(2));
+            });
+            if self.demod_config != demod_before {
+                self.rebuild_decoder();
+            }
+
+            ui.separator();
+
+            ui.label("CRC:");
+            let crc_before = self.packet_config.crc.clone();
+            egui::ComboBox::from_id_salt("crc_polynomial")
+                .selected_text(self.packet_config.crc.polynomial.as_string())
+                .show_ui(ui, |ui| {
+                    for poly in CrcPolynomial::ALL {
+                        ui.selectable_value(&mut self.packet_config.crc.polynomial, poly, poly.as_string());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.add(egui::DragValue::new(&mut self.packet_config.crc.seed).hexadecimal(4, false, true));
+                ui.checkbox(&mut self.packet_config.crc.invert, "Invert");
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.packet_config.crc.bit_order, CrcBitOrder::MsbFirst, "MSB first");
+                ui.radio_value(&mut self.packet_config.crc.bit_order, CrcBitOrder::LsbFirst, "LSB first");
+            });
+            if self.packet_config.crc != crc_before {
+                self.rebuild_decoder();
+            }
+
+            ui.separator();
+
+            ui.label("Whitening:");
+            let whitening_before = self.packet_config.whitening.clone();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.packet_config.whitening.enabled, "PN9");
+                ui.label("Seed:");
+                ui.add(egui::DragValue::new(&mut self.packet_config.whitening.seed)
+                    .range(0..=0x1FF)
+                    .hexadecimal(3, false, true));
+            });
+            if self.packet_config.whitening != whitening_before {
+                self.rebuild_decoder();
+            }
+            if ui.checkbox(&mut self.packet_config.detect_inverted, "Detect inverted polarity").changed() {
+                self.rebuild_decoder();
+            }
+
+            ui.separator();
+
+            ui.label("Front end:");
+            let front_end_before = self.front_end_config.clone();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.front_end_config.dc_removal, "Remove DC");
+                ui.checkbox(&mut self.front_end_config.iq_correction, "Correct IQ imbalance");
+            });
+            if self.front_end_config != front_end_before {
+                self.front_end.set_config(self.front_end_config.clone());
+            }
+
+            ui.label("Squelch:");
+            let squelch_before = self.squelch_config.clone();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.squelch_config.enabled, "Bursts only");
+                ui.label("Margin:");
+                ui.add(egui::DragValue::new(&mut self.squelch_config.margin_db)
+                    .range(1.0..=30.0)
+                    .speed(0.5)
+                    .fixed_decimals(1)
+                    .suffix(" dB"));
+            });
+            if self.squelch_config != squelch_before {
+                self.receivers.iter_mut().for_each(|r| r.squelch.set_config(self.squelch_config.clone()));
+            }
             
             ui.separator();
             ui.heading("Statistics");
             
             ui.label(format!("Samples: {}", self.current_samples.len()));
-            ui.label(format!("Symbols: {}", self.decoded_symbols.len()));
-            ui.label(format!("Bytes: {}", self.decoded_bytes.len()));
+            ui.label(format!("Decimation: {} ({:.1} kS/s)",
+                             self.decimation_plan().as_string(),
+                             self.channel_sample_rate() / 1000.0));
+            ui.label(format!("Symbols: {}", self.receivers[0].decoder.symbols.len()));
+            ui.label(format!("Packets: {}", self.packets.len()));
+            ui.label(format!("CRC OK: {}", self.packets.iter().filter(|p| p.crc == CrcStatus::Ok).count()));
+            ui.label(format!("AFC offset: {:+.0} Hz", self.receivers[0].decoder.frequency_offset()));
+            ui.label(format!("Noise floor: {:.1} dBFS ({:.1} dBm)",
+                             self.receivers[0].decoder.noise_floor_dbfs(),
+                             self.receivers[0].decoder.noise_floor_dbfs() + self.rssi_calibration_db()));
+            ui.label(format!("Squelch: {} (floor {:.1} dBFS)",
+                             if self.receivers[0].squelch.is_open() { "open" } else { "closed" },
+                             self.receivers[0].squelch.noise_floor_dbfs()));
+            ui.label(format!("Bursts: {}", self.bursts.len()));
+            let front_end = self.front_end.estimates();
+            ui.label(format!("DC offset: I {:+.4} Q {:+.4}", front_end.dc_offset.re, front_end.dc_offset.im));
+            ui.label(format!("IQ imbalance: {:+.2} dB, {:+.2}°", front_end.gain_imbalance_db, front_end.phase_error_deg));
+            ui.label(format!("Image rejection: {:.1} dB", front_end.image_rejection_db));
+            if let Some(ref reference) = self.reference {
+                ui.label(format!("Reference: {} annotations", reference.annotations().len()));
+                for status in ReferenceStatus::ALL {
+                    ui.label(format!("  {}: {}", status.as_string(), reference.count(status)));
+                }
+                ui.label(format!("  not annotated: {}", reference.extra()));
+            }
             
-            if let Some(ref rtlsdr) = self.rtlsdr {
-                ui.separator();
-                ui.label(if rtlsdr.is_device_running() {
-                    "🟢 Device: Connected"
-                } else {
-                    "🟡 Device: Simulation"
-                });
+            ui.separator();
+            if let Some(ref source) = self.source {
+                ui.label(format!("{} Source: {}", if source.is_running() { "🟢" } else { "🟡" }, source.name()));
+                if let Some(ref serial) = self.dongle_serial {
+                    ui.label(format!("Serial: {}", serial));
+                }
+                if let Some(time) = self.block_time {
+                    ui.label(format!("Block time: {} UTC", source::format_utc(time)));
+                }
             } else {
-                ui.separator();
-                ui.label("🔴 Device: Not found");
+                ui.label("🔴 Source: Not open");
             }
         });
         
+        // Calibration wizard
+        let mut close_calibration = false;
+        let mut apply_ppm = None;
+        if let Some(ref wizard) = self.calibration {
+            egui::Window::new("Frequency calibration")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!("Transmit packets from an HC-12 on {:.3} MHz.", wizard.frequency() as f64 / 1_000_000.0));
+                    ui.label("Its own crystal error ends up in the correction, so use a module you trust.");
+                    if self.demod_config.detector != Detector::Discriminator {
+                        ui.colored_label(egui::Color32::from_rgb(255, 180, 0), "Select the discriminator detector to measure the offset.");
+                    }
+                    ui.add(egui::ProgressBar::new(wizard.measurements() as f32 / constants::CALIBRATION_MEASUREMENTS as f32)
+                        .text(format!("{} / {} packets", wizard.measurements(), constants::CALIBRATION_MEASUREMENTS)));
+                    if let Some(result) = wizard.result() {
+                        ui.label(format!("Offset: {:+.0} Hz (spread {:.0} Hz)", result.offset_hz, result.spread_hz));
+                        ui.label(format!("Correction: {} ppm", result.ppm));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(wizard.is_complete(), egui::Button::new("Apply")).clicked() {
+                            apply_ppm = wizard.result().map(|result| result.ppm);
+                            close_calibration = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close_calibration = true;
+                        }
+                    });
+                });
+        }
+        if let Some(ppm) = apply_ppm {
+            self.set_frequency_correction(ppm);
+        }
+        if close_calibration {
+            self.calibration = None;
+        }
+
         // Central panel - Visualizations
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
@@ -305,7 +1176,8 @@ impl eframe::App for HC12App {
                         // Magnitude
                         ui.heading("Signal Magnitude");
                         if !self.current_samples.is_empty() {
-                            self.visualizer.plot_magnitude(ui, &self.current_samples);
+                            let markers = self.reference_markers(self.next_sample.saturating_sub(self.current_samples.len()), 1, None);
+                            self.visualizer.plot_magnitude(ui, &self.current_samples, &markers);
                         } else {
                             ui.label("No data");
                         }
@@ -326,8 +1198,8 @@ impl eframe::App for HC12App {
 
                 // Spectrum
                 ui.heading("Filtered Energy Spectrum");
-                if !self.decoder.filtered_freq.is_empty() {
-                    self.visualizer.plot_filtered_frequency_spectrum(ui, &self.decoder.filtered_freq);
+                if !self.receivers[0].decoder.filtered_freq.is_empty() {
+                    self.visualizer.plot_filtered_frequency_spectrum(ui, &self.receivers[0].decoder.filtered_freq);
                 } else {
                     ui.label("No data");
                 }
@@ -336,8 +1208,11 @@ impl eframe::App for HC12App {
 
                 // Spectrum
                 ui.heading("Instantaneous Frequency in Time Domain");
-                if !self.decoder.instant_freq.is_empty() {
-                    self.visualizer.plot_instantaneous_frequency(ui, &self.decoder.instant_freq);
+                if !self.receivers[0].decoder.instant_freq.is_empty() {
+                    let markers = self.reference_markers(self.source_sample(self.receivers[0].decoder.instant_freq_start()),
+                                                         self.decimation_plan().decimation(),
+                                                         Some(self.receivers[0].frequency));
+                    self.visualizer.plot_instantaneous_frequency(ui, &self.receivers[0].decoder.instant_freq, &markers);
                 } else {
                     ui.label("No data");
                 }
@@ -346,19 +1221,67 @@ impl eframe::App for HC12App {
 
                 // Spectrum
                 ui.heading("Instantaneous Frequency in Frequency Domain");
-                if !self.decoder.instant_freq.is_empty() {
-                    self.visualizer.plot_fft_real(ui, &self.decoder.instant_freq);
+                if !self.receivers[0].decoder.instant_freq.is_empty() {
+                    self.visualizer.plot_fft_real(ui, &self.receivers[0].decoder.instant_freq);
                 } else {
                     ui.label("No data");
                 }
 
                 ui.separator();
 
+                // Timing recovery diagnostics
+                ui.heading("Symbol Timing Error");
+                if !self.receivers[0].decoder.timing_error.is_empty() {
+                    self.visualizer.plot_timing_error(ui, &self.receivers[0].decoder.timing_error);
+                } else {
+                    ui.label("No data");
+                }
+                ui.separator();
+
+                // Decoded packets, newest first
+                ui.heading("Decoded Packets");
+                ui.checkbox(&mut self.crc_ok_only, "Show CRC OK only");
+                if self.packets.is_empty() {
+                    ui.label("No packets decoded");
+                }
+                for packet in self.packets.iter().rev().filter(|p| !self.crc_ok_only || p.crc == CrcStatus::Ok) {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.monospace(format!("{:<6} @{:>8} bit {:>6} len {:>3} {:<8} {:>+7.0} Hz{}:", packet.channel.map_or("-".to_string(), hc12::channel_name), packet.start_sample, packet.bit_offset, packet.payload.len(), packet.crc.as_string(), packet.freq_offset, if packet.inverted { " INV" } else { "" }));
+                        ui.monospace(format!("{:>6.1} dBm SNR {:>4.1} dB", packet.signal.rssi_dbm(), packet.signal.snr_db));
+                        let quality = format!("q {:.2} weak {:>2} BER {:.0e}", packet.quality.mean_confidence, packet.quality.weak_bits, packet.quality.bit_error_probability);
+                        if packet.quality.is_marginal() {
+                            ui.colored_label(egui::Color32::from_rgb(255, 180, 0), egui::RichText::new(quality).monospace());
+                        } else {
+                            ui.monospace(quality);
+                        }
+                        ui.monospace(packet.payload_hex());
+                        ui.monospace(format!("\"{}\"", packet.payload_text()));
+                    });
+                }
+
+                ui.separator();
+
+                // Channel activity, including bursts that did not decode
+                ui.heading("Bursts");
+                if self.bursts.is_empty() {
+                    ui.label("No bursts detected");
+                }
+                for burst in self.bursts.iter().rev() {
+                    ui.monospace(format!("{:<6} @{:>8} {:>7.1} ms peak {:>6.1} dBFS {:>+7.0} Hz",
+                                         burst.channel.map_or("-".to_string(), hc12::channel_name),
+                                         burst.start_sample,
+                                         burst.duration as f32 * 1000.0 / self.channel_sample_rate(),
+                                         burst.peak_power_dbfs,
+                                         burst.freq_offset));
+                }
+
+                ui.separator();
+
                 /*
                 // Spectrum
                 ui.heading("Filtered Frequency in Frequency Domain");
-                if !self.decoder.filtered_freq.is_empty() {
-                    self.visualizer.plot_fft_real(ui, &self.decoder.filtered_freq);
+                if !self.receivers[0].decoder.filtered_freq.is_empty() {
+                    self.visualizer.plot_fft_real(ui, &self.receivers[0].decoder.filtered_freq);
                 } else {
                     ui.label("No data");
                 }
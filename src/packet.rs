@@ -0,0 +1,457 @@
+use crate::constants;
+use crate::crc::{CrcBitOrder, CrcConfig, CrcStatus};
+use crate::power::SignalStrength;
+use crate::whitening::{Pn9, WhiteningConfig};
+
+/// SI4463 packet handler settings used to frame the demodulated bit stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketConfig {
+    pub preamble_min_bits: usize, // alternating bits required before the sync search
+    pub sync_word: u32,           // sync word, transmitted MSB first
+    pub sync_bits: usize,         // sync word length in bits (8 ... 32)
+    pub sync_max_errors: u32,     // tolerated bit errors in the sync word
+    pub max_payload_len: usize,   // longest payload accepted from the length field
+    pub crc: CrcConfig,           // CRC over length byte and payload
+    pub whitening: WhiteningConfig, // PN9 whitening of length, payload and CRC
+    pub detect_inverted: bool,    // also accept the bit inverted sync word and invert the packet
+}
+
+impl Default for PacketConfig {
+    fn default() -> Self {
+        Self {
+            preamble_min_bits: constants::HC12_PREAMBLE_MIN_BITS,
+            sync_word: constants::HC12_DEFAULT_SYNC_WORD,
+            sync_bits: constants::HC12_DEFAULT_SYNC_BITS,
+            sync_max_errors: 0,
+            max_payload_len: constants::HC12_MAX_PAYLOAD_LENGTH,
+            crc: CrcConfig::default(),
+            whitening: WhiteningConfig::default(),
+            detect_inverted: true,
+        }
+    }
+}
+
+impl PacketConfig {
+    /// Bits of a frame as the SI4463 sends it: preamble, sync word, then length, payload and
+    /// CRC, whitened if enabled. The inverse of what `PacketFramer` accepts.
+    pub fn encode(&self, payload: &[u8]) -> Vec<bool> {
+        let payload = &payload[..payload.len().min(self.max_payload_len).min(u8::MAX as usize)];
+        let mut data = vec![payload.len() as u8];
+        data.extend_from_slice(payload);
+        let crc = self.crc.compute(&data);
+        let crc_bytes = (0..self.crc.num_bytes()).map(|i| (crc >> (8 * i)) as u8);
+        match self.crc.bit_order {
+            CrcBitOrder::MsbFirst => data.extend(crc_bytes.rev()),
+            CrcBitOrder::LsbFirst => data.extend(crc_bytes),
+        }
+        if self.whitening.enabled {
+            let mut pn9 = Pn9::new(self.whitening.seed);
+            data.iter_mut().for_each(|byte| *byte = pn9.dewhiten(*byte));
+        }
+
+        // Twice the preamble the framer needs, rounded up to whole 0xAA bytes
+        let preamble_bits = (2 * self.preamble_min_bits).div_ceil(8) * 8;
+        let mut bits: Vec<bool> = (0..preamble_bits).map(|i| i % 2 == 0).collect();
+        bits.extend((0..self.sync_bits).rev().map(|i| (self.sync_word >> i) & 1 == 1));
+        bits.extend(data.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)));
+        bits
+    }
+}
+
+/// A single HC-12 frame recovered from the bit stream.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub payload: Vec<u8>,
+    pub start_sample: usize, // sample index of the first preamble bit
+    pub end_sample: usize,   // sample index of the last CRC bit
+    pub bit_offset: usize,   // bit index of the first preamble bit
+    pub crc: CrcStatus,
+    pub freq_offset: f32,    // carrier offset measured by the AFC (Hz)
+    pub inverted: bool,      // received with inverted mark/space polarity
+    pub soft_bits: Vec<f32>, // soft decisions of length, payload and CRC bits, polarity corrected
+    pub quality: PacketQuality,
+    pub signal: SignalStrength,
+    pub channel: Option<u8>, // HC-12 channel the packet was received on
+}
+
+impl Packet {
+    /// Payload as space separated hex string.
+    pub fn payload_hex(&self) -> String {
+        self.payload.iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Payload as text, non printable bytes replaced by '.'.
+    pub fn payload_text(&self) -> String {
+        self.payload.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect()
+    }
+}
+
+/// Reception quality derived from the soft decisions of a packet.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PacketQuality {
+    pub mean_confidence: f32,       // mean |soft value|, 1.0 for a clean signal
+    pub weak_bits: usize,           // bits with |soft value| below `SOFT_WEAK_THRESHOLD`
+    pub bit_error_probability: f32, // estimated from the spread of the soft values
+}
+
+impl PacketQuality {
+    /// Models each soft value as ±|value| plus Gaussian noise with standard deviation `noise`
+    /// (in soft value units), so a bit fails with probability Q(|value| / noise).
+    pub fn from_soft_bits(soft_bits: &[f32], noise: f32) -> Self {
+        if soft_bits.is_empty() {
+            return Self::default();
+        }
+        let n = soft_bits.len() as f32;
+        let sigma = noise.max(f32::EPSILON) * std::f32::consts::SQRT_2;
+
+        Self {
+            mean_confidence: soft_bits.iter().map(|s| s.abs()).sum::<f32>() / n,
+            weak_bits: soft_bits.iter().filter(|s| s.abs() < constants::SOFT_WEAK_THRESHOLD).count(),
+            bit_error_probability: soft_bits.iter().map(|s| 0.5 * erfc(s.abs() / sigma)).sum::<f32>() / n,
+        }
+    }
+
+    /// True for receptions close to failing, worth flagging in the log.
+    pub fn is_marginal(&self) -> bool {
+        self.weak_bits > 0 || self.bit_error_probability > constants::MARGINAL_BIT_ERROR_PROBABILITY
+    }
+}
+
+/// Complementary error function (Abramowitz & Stegun 7.1.26, error < 1.5e-7).
+pub fn erfc(x: f32) -> f32 {
+    let z = x.abs() as f64;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let result = poly * (-z * z).exp();
+    (if x >= 0.0 { result } else { 2.0 - result }) as f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FramerState {
+    /// Waiting for a run of alternating preamble bits.
+    Preamble,
+    /// Preamble found, shifting bits through the sync word correlator.
+    Sync { bits_since_preamble: usize },
+    /// Sync word matched, collecting the length byte.
+    Length,
+    /// Collecting `remaining` payload and CRC bytes.
+    Payload { remaining: usize },
+}
+
+/// Bit level state machine: preamble detection, sync word search and length field.
+pub struct PacketFramer {
+    config: PacketConfig,
+    state: FramerState,
+    bit_count: usize,
+    last_bit: bool,
+    run_length: usize,
+    run_start_bit: usize,
+    run_start_sample: usize,
+    sync_register: u32,
+    byte: u8,
+    byte_bits: usize,
+    length: u8,
+    inverted: bool,
+    pn9: Pn9,
+    packet: Option<Packet>,
+}
+
+impl PacketFramer {
+    pub fn new(config: PacketConfig) -> Self {
+        let pn9 = Pn9::new(config.whitening.seed);
+        Self {
+            config,
+            state: FramerState::Preamble,
+            bit_count: 0,
+            last_bit: false,
+            run_length: 0,
+            run_start_bit: 0,
+            run_start_sample: 0,
+            sync_register: 0,
+            byte: 0,
+            byte_bits: 0,
+            length: 0,
+            inverted: false,
+            pn9,
+            packet: None,
+        }
+    }
+
+    /// Feeds one soft decision, its sign is the hard bit. `sample_index` is the sample the bit
+    /// was taken from. Returns a packet when its last payload bit has been received.
+    pub fn push(&mut self, soft: f32, sample_index: usize) -> Option<Packet> {
+        let bit = soft > 0.0;
+        let bit_index = self.bit_count;
+        self.bit_count += 1;
+
+        // Track the current run of alternating bits (0xAA / 0x55 preamble)
+        if self.run_length > 0 && bit != self.last_bit {
+            self.run_length += 1;
+        } else {
+            self.run_length = 1;
+            self.run_start_bit = bit_index;
+            self.run_start_sample = sample_index;
+        }
+        self.last_bit = bit;
+
+        let sync_mask = if self.config.sync_bits >= 32 { u32::MAX } else { (1u32 << self.config.sync_bits) - 1 };
+        self.sync_register = ((self.sync_register << 1) | bit as u32) & sync_mask;
+
+        match self.state {
+            FramerState::Preamble => {
+                if self.run_length >= self.config.preamble_min_bits {
+                    self.packet = Some(Packet {
+                        payload: Vec::new(),
+                        start_sample: self.run_start_sample,
+                        end_sample: self.run_start_sample,
+                        bit_offset: self.run_start_bit,
+                        crc: CrcStatus::Disabled,
+                        freq_offset: 0.0,
+                        inverted: false,
+                        soft_bits: Vec::new(),
+                        quality: PacketQuality::default(),
+                        signal: SignalStrength::default(),
+                        channel: None,
+                    });
+                    self.state = FramerState::Sync { bits_since_preamble: 0 };
+                }
+                None
+            }
+            FramerState::Sync { bits_since_preamble } => {
+                let errors = (self.sync_register ^ (self.config.sync_word & sync_mask)).count_ones();
+                let inverted_errors = (self.sync_register ^ (!self.config.sync_word & sync_mask)).count_ones();
+                let inverted = self.config.detect_inverted && inverted_errors <= self.config.sync_max_errors;
+                if errors <= self.config.sync_max_errors || inverted {
+                    // Mark/space swapped: invert all following bits
+                    self.inverted = inverted && errors > self.config.sync_max_errors;
+                    if let Some(ref mut packet) = self.packet {
+                        packet.inverted = self.inverted;
+                    }
+                    self.byte = 0;
+                    self.byte_bits = 0;
+                    self.pn9 = Pn9::new(self.config.whitening.seed);
+                    self.state = FramerState::Length;
+                } else if self.run_length >= self.config.preamble_min_bits {
+                    // Still inside the preamble
+                    self.state = FramerState::Sync { bits_since_preamble: 0 };
+                } else if bits_since_preamble + 1 > self.config.sync_bits {
+                    // Preamble ended without a sync word
+                    self.abort();
+                } else {
+                    self.state = FramerState::Sync { bits_since_preamble: bits_since_preamble + 1 };
+                }
+                None
+            }
+            FramerState::Length => {
+                self.push_soft(soft);
+                let length = self.shift_byte(bit ^ self.inverted)? as usize;
+                if length == 0 || length > self.config.max_payload_len {
+                    self.abort();
+                } else {
+                    self.length = length as u8;
+                    self.state = FramerState::Payload { remaining: length + self.config.crc.num_bytes() };
+                }
+                None
+            }
+            FramerState::Payload { remaining } => {
+                self.push_soft(soft);
+                let byte = self.shift_byte(bit ^ self.inverted)?;
+                let mut packet = self.packet.take()?;
+                packet.payload.push(byte);
+                if remaining > 1 {
+                    self.packet = Some(packet);
+                    self.state = FramerState::Payload { remaining: remaining - 1 };
+                    None
+                } else {
+                    self.state = FramerState::Preamble;
+                    packet.end_sample = sample_index;
+                    self.check_crc(&mut packet);
+                    Some(packet)
+                }
+            }
+        }
+    }
+
+    /// Records the soft value of a bit after the sync word.
+    fn push_soft(&mut self, soft: f32) {
+        let soft = if self.inverted { -soft } else { soft };
+        if let Some(ref mut packet) = self.packet {
+            packet.soft_bits.push(soft);
+        }
+    }
+
+    /// Shifts a bit MSB first into the current byte, returning the byte once complete.
+    /// Whitening is removed here, so the length field and CRC check see the original data.
+    fn shift_byte(&mut self, bit: bool) -> Option<u8> {
+        self.byte = (self.byte << 1) | bit as u8;
+        self.byte_bits += 1;
+        if self.byte_bits < 8 {
+            return None;
+        }
+        let byte = if self.config.whitening.enabled { self.pn9.dewhiten(self.byte) } else { self.byte };
+        self.byte = 0;
+        self.byte_bits = 0;
+        Some(byte)
+    }
+
+    /// Splits the trailing CRC bytes off the payload and validates them.
+    fn check_crc(&self, packet: &mut Packet) {
+        let received = packet.payload.split_off(self.length as usize);
+        let mut data = Vec::with_capacity(packet.payload.len() + 1);
+        data.push(self.length);
+        data.extend_from_slice(&packet.payload);
+        packet.crc = self.config.crc.check(&data, &received);
+    }
+
+    /// Drops a packet in progress and starts searching for a preamble.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    /// True while no preamble has been detected.
+    pub fn is_searching(&self) -> bool {
+        self.state == FramerState::Preamble
+    }
+
+    fn abort(&mut self) {
+        self.packet = None;
+        self.state = FramerState::Preamble;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes hard bits as soft decisions of ±1, one sample per bit, numbered from `first_sample`.
+    fn push_bits(framer: &mut PacketFramer, bits: &[bool], first_sample: usize) -> Vec<Packet> {
+        bits.iter()
+            .enumerate()
+            .filter_map(|(i, &bit)| framer.push(if bit { 1.0 } else { -1.0 }, first_sample + i))
+            .collect()
+    }
+
+    fn to_bits(value: u32, count: usize) -> Vec<bool> {
+        (0..count).rev().map(|i| (value >> i) & 1 == 1).collect()
+    }
+
+    #[test]
+    fn encoded_frame_layout() {
+        let config = PacketConfig::default();
+        let bits = config.encode(b"Hi");
+        let preamble = (2 * config.preamble_min_bits).div_ceil(8) * 8;
+        assert!(bits[..preamble].iter().enumerate().all(|(i, &bit)| bit == (i % 2 == 0)));
+        assert_eq!(&bits[preamble..preamble + 16], &to_bits(0x2DD4, 16)[..]);
+        assert_eq!(&bits[preamble + 16..preamble + 24], &to_bits(2, 8)[..]);
+        assert_eq!(bits.len(), preamble + 16 + 8 * (1 + 2 + config.crc.num_bytes()));
+    }
+
+    #[test]
+    fn frames_packet() {
+        let config = PacketConfig::default();
+        let bits = config.encode(b"HC-12 test");
+        let mut framer = PacketFramer::new(config.clone());
+        let packets = push_bits(&mut framer, &bits, 1000);
+        assert_eq!(packets.len(), 1);
+        let packet = &packets[0];
+        assert_eq!(packet.payload, b"HC-12 test");
+        assert_eq!(packet.crc, CrcStatus::Ok);
+        assert!(!packet.inverted);
+        assert_eq!(packet.start_sample, 1000);
+        assert_eq!(packet.end_sample, 1000 + bits.len() - 1);
+        assert_eq!(packet.soft_bits.len(), 8 * (1 + 10 + config.crc.num_bytes()));
+    }
+
+    #[test]
+    fn crc_failure() {
+        let config = PacketConfig::default();
+        let mut bits = config.encode(b"HC-12 test");
+        let last_payload_bit = bits.len() - 8 * config.crc.num_bytes() - 1;
+        bits[last_payload_bit] = !bits[last_payload_bit];
+        let packets = push_bits(&mut PacketFramer::new(config), &bits, 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].crc, CrcStatus::Failed);
+        assert_eq!(packets[0].payload, b"HC-12 tesu");
+    }
+
+    #[test]
+    fn crc_disabled() {
+        let mut config = PacketConfig::default();
+        config.crc.polynomial = crate::crc::CrcPolynomial::None;
+        let packets = push_bits(&mut PacketFramer::new(config.clone()), &config.encode(b"abc"), 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].crc, CrcStatus::Disabled);
+        assert_eq!(packets[0].payload, b"abc");
+    }
+
+    #[test]
+    fn packet_split_across_pushes() {
+        let config = PacketConfig::default();
+        let bits = config.encode(b"split");
+        let (first, second) = bits.split_at(bits.len() / 2);
+        let mut framer = PacketFramer::new(config);
+        assert!(push_bits(&mut framer, first, 0).is_empty());
+        assert!(!framer.is_searching());
+        let packets = push_bits(&mut framer, second, first.len());
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].payload, b"split");
+        assert_eq!(packets[0].crc, CrcStatus::Ok);
+        assert_eq!(packets[0].start_sample, 0);
+    }
+
+    #[test]
+    fn consecutive_packets() {
+        let config = PacketConfig::default();
+        let mut bits = config.encode(b"one");
+        bits.extend(config.encode(b"two"));
+        let packets = push_bits(&mut PacketFramer::new(config), &bits, 0);
+        let payloads: Vec<&[u8]> = packets.iter().map(|p| p.payload.as_slice()).collect();
+        assert_eq!(payloads, [b"one", b"two"]);
+    }
+
+    #[test]
+    fn inverted_polarity() {
+        let config = PacketConfig::default();
+        let bits: Vec<bool> = config.encode(b"inverted").iter().map(|&bit| !bit).collect();
+        let packets = push_bits(&mut PacketFramer::new(config.clone()), &bits, 0);
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].inverted);
+        assert_eq!(packets[0].payload, b"inverted");
+        assert_eq!(packets[0].crc, CrcStatus::Ok);
+
+        let config = PacketConfig { detect_inverted: false, ..config };
+        assert!(push_bits(&mut PacketFramer::new(config), &bits, 0).is_empty());
+    }
+
+    #[test]
+    fn whitened() {
+        let mut config = PacketConfig::default();
+        config.whitening.enabled = true;
+        let bits = config.encode(b"whitened");
+        let packets = push_bits(&mut PacketFramer::new(config.clone()), &bits, 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].payload, b"whitened");
+        assert_eq!(packets[0].crc, CrcStatus::Ok);
+
+        config.whitening.enabled = false;
+        let packets = push_bits(&mut PacketFramer::new(config), &bits, 0);
+        assert!(packets.iter().all(|p| p.crc != CrcStatus::Ok));
+    }
+
+    #[test]
+    fn rejects_bad_length() {
+        let config = PacketConfig::default();
+        let preamble = (2 * config.preamble_min_bits).div_ceil(8) * 8;
+        let mut bits = config.encode(b"x");
+        let length = preamble + config.sync_bits;
+        bits.splice(length..length + 8, to_bits(0, 8));
+        let mut framer = PacketFramer::new(config);
+        assert!(push_bits(&mut framer, &bits, 0).is_empty());
+        assert!(framer.is_searching());
+    }
+}
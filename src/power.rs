@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use num_complex::Complex32;
+use crate::constants;
+
+/// Signal strength of a received packet.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SignalStrength {
+    pub rssi_dbfs: f32,        // mean in-channel power during the packet
+    pub noise_floor_dbfs: f32, // in-channel power between packets
+    pub snr_db: f32,
+    pub calibration_db: f32,   // added to dBFS values to approximate dBm
+}
+
+impl SignalStrength {
+    pub fn rssi_dbm(&self) -> f32 {
+        self.rssi_dbfs + self.calibration_db
+    }
+
+    pub fn noise_floor_dbm(&self) -> f32 {
+        self.noise_floor_dbfs + self.calibration_db
+    }
+}
+
+/// Converts a mean power relative to full scale into dBFS.
+pub fn to_dbfs(power: f32) -> f32 {
+    10.0 * (power + 1e-12).log10()
+}
+
+/// Measures the in-channel power of the filtered IQ stream in blocks of `POWER_BLOCK_SIZE`
+/// samples. The recent blocks are kept so the power of a packet can be looked up once the
+/// framer has found its end. The noise floor is a low percentile of the same history, which
+/// ignores packets as long as the channel is not busy most of the time.
+pub struct PowerMeter {
+    blocks: VecDeque<(usize, f32)>, // stream index of the first sample and mean power of the recent blocks
+    next_sample: usize,             // stream index of the next input sample
+    settle: usize,                  // samples still to be skipped after a restart
+    noise_reference: Option<f32>,   // noise floor measured elsewhere, replaces the percentile
+    sum: f32,                       // power of the block being accumulated
+    count: usize,
+}
+
+impl PowerMeter {
+    pub fn new() -> Self {
+        Self {
+            blocks: VecDeque::with_capacity(constants::POWER_HISTORY_BLOCKS),
+            next_sample: 0,
+            settle: 0,
+            noise_reference: None,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Continues measuring at stream index `start_sample`, ignoring the first `settle` samples
+    /// while the filter ahead of the meter fills up. The history is kept.
+    pub fn restart(&mut self, start_sample: usize, settle: usize) {
+        self.next_sample = start_sample;
+        self.settle = settle;
+        self.sum = 0.0;
+        self.count = 0;
+    }
+
+    /// Uses an externally measured in-channel noise power as noise floor. Needed when only
+    /// bursts are measured, the history then holds too few blocks between packets.
+    pub fn set_noise_reference(&mut self, noise_power: Option<f32>) {
+        self.noise_reference = noise_power;
+    }
+
+    pub fn process(&mut self, iq: &[Complex32]) {
+        for sample in iq {
+            self.next_sample += 1;
+            if self.settle > 0 {
+                self.settle -= 1;
+                continue;
+            }
+            self.sum += sample.norm_sqr();
+            self.count += 1;
+            if self.count == constants::POWER_BLOCK_SIZE {
+                self.push_block(self.next_sample - self.count, self.sum / self.count as f32);
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    fn push_block(&mut self, start_sample: usize, power: f32) {
+        if self.blocks.len() == constants::POWER_HISTORY_BLOCKS {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back((start_sample, power));
+    }
+
+    pub fn noise_floor_dbfs(&self) -> f32 {
+        to_dbfs(self.noise_floor())
+    }
+
+    fn noise_floor(&self) -> f32 {
+        if let Some(noise_power) = self.noise_reference {
+            return noise_power;
+        }
+        if self.blocks.is_empty() {
+            return 0.0;
+        }
+        let mut powers: Vec<f32> = self.blocks.iter().map(|&(_, power)| power).collect();
+        let index = ((powers.len() - 1) as f32 * constants::NOISE_FLOOR_PERCENTILE) as usize;
+        *powers.select_nth_unstable_by(index, |a, b| a.total_cmp(b)).1
+    }
+
+    /// Signal strength over the stream samples `start..=end`.
+    pub fn signal_strength(&self, start: usize, end: usize, calibration_db: f32) -> SignalStrength {
+        let powers: Vec<f32> = self.blocks.iter()
+            .filter(|&&(block_start, _)| block_start + constants::POWER_BLOCK_SIZE > start && block_start <= end)
+            .map(|&(_, power)| power)
+            .collect();
+        let power = if powers.is_empty() { 0.0 } else { powers.iter().sum::<f32>() / powers.len() as f32 };
+
+        // The packet power contains the noise as well, remove it before forming the SNR
+        let noise = self.noise_floor();
+        SignalStrength {
+            rssi_dbfs: to_dbfs(power),
+            noise_floor_dbfs: to_dbfs(noise),
+            snr_db: to_dbfs((power - noise).max(0.0) / noise.max(1e-12)),
+            calibration_db,
+        }
+    }
+}
+
+impl Default for PowerMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
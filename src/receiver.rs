@@ -0,0 +1,48 @@
+use num_complex::Complex32;
+use crate::bitrate::BitRateEstimate;
+use crate::hc12_decoder::HC12Decoder;
+use crate::packet::Packet;
+use crate::squelch::{Burst, BurstDetector};
+
+/// Squelch and decoder for one channel stream.
+pub struct ChannelReceiver {
+    pub channel: Option<u8>, // HC-12 channel number, `None` when tuned off-grid
+    pub frequency: u32,      // channel centre (Hz)
+    pub squelch: BurstDetector,
+    pub decoder: HC12Decoder,
+    pub detected_rate: Option<BitRateEstimate>, // air rate of the last burst classified on the channel
+}
+
+impl ChannelReceiver {
+    pub fn new(channel: Option<u8>,
+               frequency: u32,
+               squelch: BurstDetector,
+               decoder: HC12Decoder,
+               detected_rate: Option<BitRateEstimate>) -> Self {
+        Self {
+            channel,
+            frequency,
+            squelch,
+            decoder,
+            detected_rate,
+        }
+    }
+
+    /// Runs the next buffer of the channel stream through the squelch and demodulates what it
+    /// passes on. Returns the decoded packets and the bursts that ended, tagged with the channel.
+    pub fn process(&mut self, samples: &[Complex32]) -> Result<(Vec<Packet>, Vec<Burst>), String> {
+        let output = self.squelch.process(samples);
+
+        // Between bursts the decoder sees no samples, so it takes the noise floor from the squelch
+        self.decoder.set_input_noise_floor(self.squelch.is_enabled().then(|| self.squelch.noise_floor_dbfs()));
+        let mut packets = Vec::new();
+        for segment in output.segments {
+            packets.extend(self.decoder.demodulate_at(segment.start_sample, &segment.samples)?);
+        }
+
+        packets.iter_mut().for_each(|packet| packet.channel = self.channel);
+        let mut bursts = output.bursts;
+        bursts.iter_mut().for_each(|burst| burst.channel = self.channel);
+        Ok((packets, bursts))
+    }
+}
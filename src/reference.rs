@@ -0,0 +1,198 @@
+use crate::sigmf::Annotation;
+
+/// How the decoder did on an annotated packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReferenceStatus {
+    /// Not yet reached by the decoder.
+    Pending,
+    /// Decoded, with the annotated payload if the annotation has one.
+    Matched,
+    /// Decoded, but with a different payload.
+    PayloadDiffers,
+    /// Played past without a packet.
+    Missed,
+    /// Not played completely, playback jumped past its start.
+    Skipped,
+}
+
+impl ReferenceStatus {
+    pub const ALL: [ReferenceStatus; 5] = [ReferenceStatus::Pending,
+                                           ReferenceStatus::Matched,
+                                           ReferenceStatus::PayloadDiffers,
+                                           ReferenceStatus::Missed,
+                                           ReferenceStatus::Skipped];
+
+    pub fn as_string(self) -> String {
+        match self {
+            ReferenceStatus::Pending => "pending".to_string(),
+            ReferenceStatus::Matched => "matched".to_string(),
+            ReferenceStatus::PayloadDiffers => "payload differs".to_string(),
+            ReferenceStatus::Missed => "missed".to_string(),
+            ReferenceStatus::Skipped => "skipped".to_string(),
+        }
+    }
+}
+
+/// Packets annotated in a recording, such as by a colleague's receiver, checked against what
+/// the decoder makes of the same samples. Sample indices are those of the recording, which
+/// are the stream indices of its playback.
+pub struct ReferenceAnnotations {
+    annotations: Vec<Annotation>,
+    status: Vec<ReferenceStatus>,
+    extra: Vec<usize>, // start samples of decoded packets without an annotation
+}
+
+impl ReferenceAnnotations {
+    pub fn new(annotations: Vec<Annotation>) -> Self {
+        Self {
+            status: vec![ReferenceStatus::Pending; annotations.len()],
+            annotations,
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn status(&self, index: usize) -> ReferenceStatus {
+        self.status[index]
+    }
+
+    /// Annotations in a state.
+    pub fn count(&self, status: ReferenceStatus) -> usize {
+        self.status.iter().filter(|&&s| s == status).count()
+    }
+
+    /// Decoded packets that no annotation accounts for.
+    pub fn extra(&self) -> usize {
+        self.extra.len()
+    }
+
+    /// Continues the comparison at `sample`, as when playback jumps there. What lies from there
+    /// on is played again and checked afresh. Pending annotations that begin before it cannot be
+    /// decoded in full and are skipped rather than missed, those already checked keep their result.
+    pub fn restart_at(&mut self, sample: usize) {
+        for (status, annotation) in self.status.iter_mut().zip(&self.annotations) {
+            if annotation.sample_start >= sample {
+                *status = ReferenceStatus::Pending;
+            } else if *status == ReferenceStatus::Pending {
+                *status = ReferenceStatus::Skipped;
+            }
+        }
+        self.extra.retain(|&start| start < sample);
+    }
+
+    /// Assigns a decoded packet to the first annotation not yet decoded that overlaps it in
+    /// time and, where the annotation gives its band, contains its frequency. Returns the
+    /// annotation's index, or `None` if the packet is extra. An annotation given up as missed
+    /// still takes a packet the decoder reports late.
+    pub fn add_decoded(&mut self, decoded: &Annotation) -> Option<usize> {
+        let decoded_end = decoded.sample_start + decoded.sample_count;
+        let frequency = decoded.freq_lower_edge.zip(decoded.freq_upper_edge).map(|(lower, upper)| (lower + upper) / 2.0);
+        let index = (0..self.annotations.len()).find(|&i| {
+            let annotation = &self.annotations[i];
+            let in_band = match (frequency, annotation.freq_lower_edge, annotation.freq_upper_edge) {
+                (Some(frequency), Some(lower), Some(upper)) => (lower..=upper).contains(&frequency),
+                _ => true,
+            };
+            matches!(self.status[i], ReferenceStatus::Pending | ReferenceStatus::Missed)
+                && decoded.sample_start < annotation.sample_start + annotation.sample_count
+                && annotation.sample_start < decoded_end
+                && in_band
+        });
+
+        match index {
+            Some(i) => {
+                self.status[i] = match (&self.annotations[i].payload, &decoded.payload) {
+                    (Some(expected), Some(payload)) if expected != payload => ReferenceStatus::PayloadDiffers,
+                    _ => ReferenceStatus::Matched,
+                };
+            }
+            None => self.extra.push(decoded.sample_start),
+        }
+        index
+    }
+
+    /// Gives up on the pending annotations that end before `sample`. Returns their indices.
+    pub fn mark_missed(&mut self, sample: usize) -> Vec<usize> {
+        let missed: Vec<usize> = (0..self.annotations.len())
+            .filter(|&i| self.status[i] == ReferenceStatus::Pending)
+            .filter(|&i| self.annotations[i].sample_start + self.annotations[i].sample_count < sample)
+            .collect();
+        missed.iter().for_each(|&i| self.status[i] = ReferenceStatus::Missed);
+        missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(sample_start: usize, payload: &[u8]) -> Annotation {
+        Annotation {
+            sample_start,
+            sample_count: 1000,
+            freq_lower_edge: None,
+            freq_upper_edge: None,
+            label: String::new(),
+            comment: String::new(),
+            crc: None,
+            payload: Some(payload.to_vec()),
+        }
+    }
+
+    #[test]
+    fn matches_decoded_packets() {
+        let mut reference = ReferenceAnnotations::new(vec![annotation(0, b"a"), annotation(5000, b"b")]);
+        assert_eq!(reference.add_decoded(&annotation(100, b"a")), Some(0));
+        assert_eq!(reference.add_decoded(&annotation(5100, b"x")), Some(1));
+        assert_eq!(reference.add_decoded(&annotation(9000, b"c")), None);
+        assert_eq!(reference.status(0), ReferenceStatus::Matched);
+        assert_eq!(reference.status(1), ReferenceStatus::PayloadDiffers);
+        assert_eq!(reference.extra(), 1);
+    }
+
+    #[test]
+    fn missed_then_decoded_late() {
+        let mut reference = ReferenceAnnotations::new(vec![annotation(0, b"a")]);
+        assert_eq!(reference.mark_missed(500), Vec::<usize>::new());
+        assert_eq!(reference.mark_missed(2000), vec![0]);
+        assert_eq!(reference.add_decoded(&annotation(100, b"a")), Some(0));
+        assert_eq!(reference.count(ReferenceStatus::Missed), 0);
+    }
+
+    #[test]
+    fn seek_skips_rather_than_misses() {
+        let mut reference = ReferenceAnnotations::new(vec![annotation(0, b"a"), annotation(5000, b"b"), annotation(20_000, b"c")]);
+        reference.add_decoded(&annotation(12_000, b"x"));
+        reference.restart_at(10_000);
+        assert_eq!(reference.extra(), 0);
+        assert_eq!(reference.mark_missed(15_000), Vec::<usize>::new());
+        assert_eq!(reference.count(ReferenceStatus::Skipped), 2);
+        assert_eq!(reference.count(ReferenceStatus::Pending), 1);
+        assert_eq!(reference.add_decoded(&annotation(5100, b"b")), None);
+    }
+
+    #[test]
+    fn seek_keeps_checked_results() {
+        let mut reference = ReferenceAnnotations::new(vec![annotation(0, b"a"), annotation(5000, b"b"), annotation(20_000, b"c")]);
+        assert_eq!(reference.add_decoded(&annotation(100, b"a")), Some(0));
+        assert_eq!(reference.add_decoded(&annotation(3000, b"x")), None);
+        assert_eq!(reference.add_decoded(&annotation(20_100, b"c")), Some(2));
+        assert_eq!(reference.add_decoded(&annotation(25_000, b"y")), None);
+
+        // Forward past the second packet, then back before the third
+        reference.restart_at(10_000);
+        assert_eq!(reference.status(0), ReferenceStatus::Matched);
+        assert_eq!(reference.status(1), ReferenceStatus::Skipped);
+        assert_eq!(reference.status(2), ReferenceStatus::Pending);
+        assert_eq!(reference.extra(), 1);
+
+        assert_eq!(reference.add_decoded(&annotation(20_100, b"c")), Some(2));
+        reference.restart_at(15_000);
+        assert_eq!(reference.count(ReferenceStatus::Matched), 1);
+        assert_eq!(reference.count(ReferenceStatus::Skipped), 1);
+        assert_eq!(reference.count(ReferenceStatus::Pending), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::constants;
+use crate::hc12_decoder::LowPassFilter;
+
+/// Rational polyphase resampler (interpolate by L, filter, decimate by M) for real samples.
+/// The ratio is exact, so no timing drift accumulates over long packets.
+pub struct Resampler {
+    interpolation: usize,  // L
+    decimation: usize,     // M
+    phases: Vec<Vec<f32>>, // polyphase branches of the anti-aliasing filter
+    history: Vec<f32>,     // last input samples of the previous call
+    next: usize,           // upsampled index of the next output, relative to the current call
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let (interpolation, decimation) = Self::ratio(input_rate, output_rate);
+        let taps_per_phase = constants::RESAMPLER_TAPS_PER_PHASE;
+
+        // Prototype low-pass at the upsampled rate, cut off below the lower of both Nyquist rates
+        let prototype = LowPassFilter::build_kernel(
+            interpolation as f32,
+            0.45 * (interpolation as f32 / decimation as f32).min(1.0),
+            interpolation * taps_per_phase,
+        );
+
+        let phases = (0..interpolation)
+            .map(|p| (0..taps_per_phase)
+                .map(|j| prototype[p + j * interpolation] * interpolation as f32)
+                .collect())
+            .collect();
+
+        Self {
+            interpolation,
+            decimation,
+            phases,
+            history: vec![0.0; taps_per_phase - 1],
+            next: 0,
+        }
+    }
+
+    /// Reduces input/output rate to the smallest L/M. Ratios that need more than
+    /// `RESAMPLER_MAX_INTERPOLATION` phases are approximated, the timing loop absorbs the rest.
+    fn ratio(input_rate: u32, output_rate: u32) -> (usize, usize) {
+        fn gcd(a: u64, b: u64) -> u64 { if b == 0 { a } else { gcd(b, a % b) } }
+
+        let divisor = gcd(input_rate as u64, output_rate as u64).max(1);
+        let (l, m) = ((output_rate as u64 / divisor) as usize, (input_rate as u64 / divisor) as usize);
+        if l <= constants::RESAMPLER_MAX_INTERPOLATION {
+            return (l.max(1), m.max(1));
+        }
+        let l = constants::RESAMPLER_MAX_INTERPOLATION;
+        let m = (input_rate as f64 * l as f64 / output_rate as f64).round() as usize;
+        (l, m.max(1))
+    }
+
+    /// Input samples per output sample.
+    pub fn step(&self) -> f64 {
+        self.decimation as f64 / self.interpolation as f64
+    }
+
+    /// Maps an output index back to the input sample it was centred on, including the filter delay.
+    pub fn input_index(&self, output_index: usize) -> usize {
+        let delay = constants::RESAMPLER_TAPS_PER_PHASE as f64 / 2.0;
+        (output_index as f64 * self.step() - delay).max(0.0).round() as usize
+    }
+
+    /// Clears the history and restarts the output at the next input sample.
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|v| *v = 0.0);
+        self.next = 0;
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        let delay = self.history.len();
+        let mut buffer = Vec::with_capacity(delay + input.len());
+        buffer.extend_from_slice(&self.history);
+        buffer.extend_from_slice(input);
+
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step()) as usize + 1);
+        while self.next / self.interpolation < input.len() {
+            let n = delay + self.next / self.interpolation;
+            let phase = &self.phases[self.next % self.interpolation];
+            let value = phase.iter()
+                .enumerate()
+                .fold(0.0, |acc, (j, &h)| acc + h * buffer[n - j]);
+            output.push(value);
+            self.next += self.decimation;
+        }
+
+        self.next -= input.len() * self.interpolation;
+        self.history.copy_from_slice(&buffer[buffer.len() - delay..]);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratio_reduced_by_gcd() {
+        assert_eq!(Resampler::ratio(48_000, 32_000), (2, 3));
+        assert_eq!(Resampler::ratio(2_048_000, 80_000), (5, 128));
+        assert_eq!(Resampler::ratio(64_000, 64_000), (1, 1));
+        assert_eq!(Resampler::ratio(10_000, 40_000), (4, 1));
+    }
+
+    #[test]
+    fn ratio_capped() {
+        // 1 000 003 is prime, the exact ratio would need 40 000 phases
+        let (l, m) = Resampler::ratio(1_000_003, 40_000);
+        assert_eq!(l, constants::RESAMPLER_MAX_INTERPOLATION);
+        assert_eq!(m, (1_000_003.0 * l as f64 / 40_000.0).round() as usize);
+        assert_eq!(Resampler::ratio(512_000, 1_000), (1, 512));
+    }
+
+    #[test]
+    fn chunk_size_independent() {
+        let input: Vec<f32> = (0..5000).map(|n| (n as f32 * 0.05).sin() + 0.3 * (n as f32 * 0.31).cos()).collect();
+        for (input_rate, output_rate) in [(48_000, 32_000), (2_048_000, 80_000), (1_000_003, 40_000)] {
+            let whole = Resampler::new(input_rate, output_rate).process(&input);
+
+            let mut resampler = Resampler::new(input_rate, output_rate);
+            let mut split = Vec::new();
+            let mut rest = &input[..];
+            for size in [1, 0, 7, 128, 1000, 3].iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+                split.extend(resampler.process(chunk));
+                rest = tail;
+            }
+            assert_eq!(split, whole, "{} -> {}", input_rate, output_rate);
+        }
+    }
+}
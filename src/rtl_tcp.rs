@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use crate::constants;
+use crate::source::{convert_cu8, SampleBlock, SampleSource};
+
+/// rtl_tcp command codes, each sent as the code followed by a big endian 32 bit value.
+const SET_FREQUENCY: u8 = 0x01;
+const SET_SAMPLE_RATE: u8 = 0x02;
+const SET_GAIN_MODE: u8 = 0x03;
+const SET_GAIN: u8 = 0x04;
+const SET_FREQUENCY_CORRECTION: u8 = 0x05;
+
+/// Dongle shared over the network by `rtl_tcp`. The server streams the same unsigned 8 bit
+/// IQ as a local dongle, after a 12 byte header starting with `RTL0`.
+pub struct RtlTcpSource {
+    address: String,
+    stream: TcpStream, // command direction
+    sample_rx: Receiver<SampleBlock>,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl RtlTcpSource {
+    /// Connects to `host:port` and starts receiving. Gain is switched to manual.
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let socket_address = address.to_socket_addrs()
+            .map_err(|e| format!("Invalid address {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("Invalid address {}", address))?;
+        let stream = TcpStream::connect_timeout(&socket_address, Duration::from_secs_f32(constants::RTL_TCP_CONNECT_TIMEOUT_S))
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        let reader = stream.try_clone().map_err(|e| e.to_string())?;
+
+        let (sample_tx, sample_rx) = unbounded();
+        let is_running = Arc::new(Mutex::new(false));
+        let is_running_clone = is_running.clone();
+        thread::spawn(move || {
+            Self::receive_thread(reader, sample_tx, is_running_clone);
+        });
+
+        let mut source = Self {
+            address: address.to_string(),
+            stream,
+            sample_rx,
+            is_running,
+        };
+        source.send(SET_GAIN_MODE, 1);
+        Ok(source)
+    }
+
+    fn receive_thread(mut stream: TcpStream, sample_tx: Sender<SampleBlock>, is_running: Arc<Mutex<bool>>) {
+        let mut header = [0u8; 12];
+        if let Err(e) = stream.read_exact(&mut header) {
+            eprintln!("Failed to read rtl_tcp header: {}", e);
+            return;
+        }
+        if &header[..4] != b"RTL0" {
+            eprintln!("Not an rtl_tcp server");
+            return;
+        }
+        *is_running.lock().unwrap() = true;
+
+        let mut buffer = vec![0u8; constants::SDR_BUFFER_SIZE];
+        let mut start_sample = 0;
+        loop {
+            if let Err(e) = stream.read_exact(&mut buffer) {
+                eprintln!("rtl_tcp connection closed: {}", e);
+                break;
+            }
+            let samples = convert_cu8(&buffer);
+            let block = SampleBlock { start_sample, timestamp: SystemTime::now(), samples };
+            start_sample += block.samples.len();
+            if sample_tx.send(block).is_err() {
+                break;
+            }
+        }
+        *is_running.lock().unwrap() = false;
+    }
+
+    fn send(&mut self, command: u8, value: u32) {
+        let mut message = [command, 0, 0, 0, 0];
+        message[1..].copy_from_slice(&value.to_be_bytes());
+        if let Err(e) = self.stream.write_all(&message) {
+            eprintln!("Failed to send rtl_tcp command {:#04x}: {}", command, e);
+        }
+    }
+}
+
+impl SampleSource for RtlTcpSource {
+    fn next_block(&mut self) -> Option<SampleBlock> {
+        self.sample_rx.try_recv().ok()
+    }
+
+    fn set_frequency(&mut self, frequency: u32) {
+        self.send(SET_FREQUENCY, frequency);
+    }
+
+    fn set_sample_rate(&mut self, rate: u32) {
+        self.send(SET_SAMPLE_RATE, rate);
+    }
+
+    fn set_gain(&mut self, gain: i32) {
+        self.send(SET_GAIN, gain as u32);
+    }
+
+    fn set_frequency_correction(&mut self, ppm: i32) {
+        self.send(SET_FREQUENCY_CORRECTION, ppm as u32);
+    }
+
+    fn is_running(&self) -> bool {
+        *self.is_running.lock().unwrap()
+    }
+
+    fn serial(&self) -> Option<String> {
+        None
+    }
+
+    fn name(&self) -> String {
+        format!("rtl_tcp {}", self.address)
+    }
+}
+
+impl Drop for RtlTcpSource {
+    fn drop(&mut self) {
+        self.stream.shutdown(Shutdown::Both).ok();
+    }
+}
@@ -1,40 +1,50 @@
 use crate::constants;
+use crate::source::{convert_cu8, SampleBlock, SampleSource};
 
-use crossbeam_channel::{Sender, Receiver, unbounded};
-use num_complex::Complex32;
+use crossbeam_channel::{Sender, Receiver, bounded, unbounded};
 use std::thread;
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 
 
 pub struct RTLSDRController {
-    sample_rx: Receiver<Vec<Complex32>>,
+    sample_rx: Receiver<SampleBlock>,
     control_tx: Option<Sender<RTLSDRCommand>>,
     is_running: Arc<Mutex<bool>>,
+    serial: Arc<Mutex<Option<String>>>,
 }
 
 pub enum RTLSDRCommand {
     SetFrequency(u32),
     SetSampleRate(u32),
     SetGain(i32),
+    SetFrequencyCorrection(i32),
     Stop,
 }
 
 impl RTLSDRController {
+    /// Opens the first dongle. The device can only be used from the thread that opened it,
+    /// so the reading thread opens it and reports back whether that worked.
     pub fn new() -> Result<Self, String> {
         let (sample_tx, sample_rx) = unbounded();
         let (control_tx, control_rx) = unbounded();
+        let (opened_tx, opened_rx) = bounded(1);
         let is_running = Arc::new(Mutex::new(false));
         let is_running_clone = is_running.clone();
+        let serial = Arc::new(Mutex::new(None));
+        let serial_clone = serial.clone();
         
         thread::spawn(move || {
-            Self::rtlsdr_thread(sample_tx, control_rx, is_running_clone);
+            Self::rtlsdr_thread(sample_tx, control_rx, opened_tx, is_running_clone, serial_clone);
         });
+        opened_rx.recv().map_err(|_| "RTL-SDR thread ended unexpectedly".to_string())??;
         
         Ok(Self {
             sample_rx,
             control_tx: Some(control_tx),
             is_running,
+            serial,
         })
     }
 
@@ -44,7 +54,9 @@ impl RTLSDRController {
     ///
     /// * `sample_tx`:
     /// * `control_rx`:
+    /// * `opened_tx`: whether the device could be opened
     /// * `is_running`:
+    /// * `serial`:
     ///
     /// returns: ()
     ///
@@ -54,9 +66,11 @@ impl RTLSDRController {
     ///
     /// ```
     fn rtlsdr_thread(
-        sample_tx: Sender<Vec<Complex32>>,
+        sample_tx: Sender<SampleBlock>,
         control_rx: Receiver<RTLSDRCommand>,
+        opened_tx: Sender<Result<(), String>>,
         is_running: Arc<Mutex<bool>>,
+        serial: Arc<Mutex<Option<String>>>,
     ) {
         // Try to initialize RTL-SDR :w
         // device
@@ -65,27 +79,17 @@ impl RTLSDRController {
         let mut device = match device_result {
             Ok(dev) => dev,
             Err(e) => {
-                eprintln!("Failed to open RTL-SDR device: {:?}", e);
-                eprintln!("Running in simulation mode...");
                 *is_running.lock().unwrap() = false;
-                
-                // Simulation mode - generate test data
-                loop {
-                    if let Ok(cmd) = control_rx.try_recv() {
-                        if matches!(cmd, RTLSDRCommand::Stop) {
-                            break;
-                        }
-                    }
-                    
-                    // Generate simulated HC12a-like signal
-                    let samples = Self::generate_test_signal();
-                    sample_tx.send(samples).ok();
-                    thread::sleep(std::time::Duration::from_millis(100));
-                }
+                opened_tx.send(Err(format!("Failed to open RTL-SDR device: {:?}", e))).ok();
                 return;
             }
         };
 
+        match device.get_usb_strings() {
+            Ok(strings) => *serial.lock().unwrap() = Some(strings.serial),
+            Err(e) => eprintln!("Failed to read serial number: {:?}", e),
+        }
+
         // Configure device
         if let Err(e) = device.set_sample_rate(constants::SDR_SAMPLE_RATE) {
             eprintln!("Failed to set sample rate: {:?}", e);
@@ -108,7 +112,9 @@ impl RTLSDRController {
         }
 
         *is_running.lock().unwrap() = true;
+        opened_tx.send(Ok(())).ok();
 
+        let mut start_sample = 0;
         loop {
             // Check for commands
             if let Ok(cmd) = control_rx.try_recv() {
@@ -122,6 +128,9 @@ impl RTLSDRController {
                     RTLSDRCommand::SetSampleRate(rate) => {
                         device.set_sample_rate(rate).ok();
                     }
+                    RTLSDRCommand::SetFrequencyCorrection(ppm) => {
+                        device.set_freq_correction(ppm).ok();
+                    }
                     RTLSDRCommand::Stop => {
                         *is_running.lock().unwrap() = false;
                         break;
@@ -132,8 +141,10 @@ impl RTLSDRController {
             // Read samples - read_sync takes length and returns Vec<u8>
             match device.read_sync(constants::SDR_BUFFER_SIZE) {
                 Ok(buffer) => {
-                    let samples = Self::convert_iq(&buffer);
-                    sample_tx.send(samples).ok();
+                    let samples = convert_cu8(&buffer);
+                    let block = SampleBlock { start_sample, timestamp: SystemTime::now(), samples };
+                    start_sample += block.samples.len();
+                    sample_tx.send(block).ok();
                 }
                 Err(e) => {
                     eprintln!("Read error: {:?}", e);
@@ -143,70 +154,46 @@ impl RTLSDRController {
         }
     }
 
-    /// Convert the buffer read from the RTLSDR dongle from [u8,u8] representing
-    /// I and Q data to [f32,f32], mapping the range 0 ... 255 to -1.0 ... +1.0.
-    fn convert_iq(buffer: &[u8]) -> Vec<Complex32> {
-        buffer.chunks_exact(2)
-            .map(|chunk| {
-                let i = (chunk[0] as f32 - 127.5) / 127.5;
-                let q = (chunk[1] as f32 - 127.5) / 127.5;
-                Complex32::new(i, q)
-            })
-            .collect()
-    }
-
-    fn generate_test_signal() -> Vec<Complex32> {
-        use std::f32::consts::PI;
-        
-        let mut samples = Vec::with_capacity(4096);
-        let mut rng_state = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u32;
-        
-        for i in 0..4096 {
-            // Simple PRNG
-            rng_state = rng_state.wrapping_mul(1664525).wrapping_add(1013904223);
-            let phase = (rng_state as f32 / u32::MAX as f32) * 2.0 * PI;
-            
-            // Add chirp-like modulation
-            let chirp = (i as f32 * 0.001).sin();
-            let amp = 0.3 + chirp * 0.2;
-            
-            samples.push(Complex32::new(
-                amp * phase.cos(),
-                amp * phase.sin(),
-            ));
+    fn send(&self, command: RTLSDRCommand) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(command).ok();
         }
-        
-        samples
     }
+}
 
-    pub fn get_samples(&self) -> Option<Vec<Complex32>> {
+impl SampleSource for RTLSDRController {
+    fn next_block(&mut self) -> Option<SampleBlock> {
         self.sample_rx.try_recv().ok()
     }
 
-    pub fn set_frequency(&self, freq: u32) {
-        if let Some(tx) = &self.control_tx {
-            tx.send(RTLSDRCommand::SetFrequency(freq)).ok();
-        }
+    fn set_frequency(&mut self, frequency: u32) {
+        self.send(RTLSDRCommand::SetFrequency(frequency));
     }
 
-    pub fn set_gain(&self, freq: i32) {
-        if let Some(tx) = &self.control_tx {
-            tx.send(RTLSDRCommand::SetGain(freq)).ok();
-        }
+    fn set_sample_rate(&mut self, rate: u32) {
+        self.send(RTLSDRCommand::SetSampleRate(rate));
     }
 
-    pub fn set_sample_rate(&self, rate: u32) {
-        if let Some(tx) = &self.control_tx {
-            tx.send(RTLSDRCommand::SetSampleRate(rate)).ok();
-        }
+    fn set_gain(&mut self, gain: i32) {
+        self.send(RTLSDRCommand::SetGain(gain));
     }
 
-    pub fn is_device_running(&self) -> bool {
+    fn set_frequency_correction(&mut self, ppm: i32) {
+        self.send(RTLSDRCommand::SetFrequencyCorrection(ppm));
+    }
+
+    fn is_running(&self) -> bool {
         *self.is_running.lock().unwrap()
     }
+
+    /// USB serial number of the opened dongle, `None` until it is open.
+    fn serial(&self) -> Option<String> {
+        self.serial.lock().unwrap().clone()
+    }
+
+    fn name(&self) -> String {
+        "RTL-SDR".to_string()
+    }
 }
 
 impl Drop for RTLSDRController {
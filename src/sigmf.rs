@@ -0,0 +1,479 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use num_complex::Complex32;
+use serde_json::{json, Map, Value};
+use crate::crc::CrcStatus;
+use crate::iq_file::{FileSettings, SampleFormat};
+use crate::source::{format_utc, SampleBlock};
+
+/// Namespace of the fields this app adds to SigMF metadata.
+const EXTENSION: &str = "hc12";
+
+/// Settings in effect when a recording starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingInfo {
+    pub sample_rate: u32,
+    pub frequency: u32,         // centre frequency (Hz)
+    pub gain: i32,              // tuner gain in tenths of a dB
+    pub ppm: i32,
+    pub serial: Option<String>, // dongle serial number
+    pub hardware: String,       // description of the source
+}
+
+/// A capture segment: from `sample_start` on, the recording was made at `frequency`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub sample_start: usize,
+    pub frequency: u32,
+    pub datetime: Option<SystemTime>,
+}
+
+/// A labelled sample range of a recording, here an HC-12 packet. Annotations read from other
+/// recorders may leave out the band and the `hc12` fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub sample_start: usize,
+    pub sample_count: usize,
+    pub freq_lower_edge: Option<f64>, // Hz
+    pub freq_upper_edge: Option<f64>,
+    pub label: String,
+    pub comment: String,
+    pub crc: Option<CrcStatus>,
+    pub payload: Option<Vec<u8>>,
+}
+
+/// Writes the samples of a source to a SigMF recording: the raw IQ as unsigned 8 bit to
+/// `.sigmf-data`, which loses nothing for the dongle's own samples, and on `finish` the
+/// settings, capture segments and packet annotations to `.sigmf-meta`.
+pub struct SigmfRecorder {
+    base_path: PathBuf,
+    data: BufWriter<File>,
+    info: RecordingInfo,
+    captures: Vec<Capture>,
+    annotations: Vec<Annotation>,
+    stream_offset: Option<isize>, // recording index minus source stream index
+    samples_written: usize,
+    finished: bool,
+}
+
+impl SigmfRecorder {
+    /// Creates `<base_path>.sigmf-data`, which must not exist yet. The metadata file is written
+    /// when the recording ends.
+    pub fn create(base_path: &Path, info: RecordingInfo) -> Result<Self, String> {
+        let data_path = base_path.with_extension("sigmf-data");
+        let file = File::create_new(&data_path).map_err(|e| format!("Failed to create {}: {}", data_path.display(), e))?;
+        Ok(Self {
+            base_path: base_path.to_path_buf(),
+            data: BufWriter::new(file),
+            captures: vec![Capture { sample_start: 0, frequency: info.frequency, datetime: None }],
+            info,
+            annotations: Vec::new(),
+            stream_offset: None,
+            samples_written: 0,
+            finished: false,
+        })
+    }
+
+    pub fn data_path(&self) -> PathBuf {
+        self.base_path.with_extension("sigmf-data")
+    }
+
+    pub fn meta_path(&self) -> PathBuf {
+        self.base_path.with_extension("sigmf-meta")
+    }
+
+    /// Annotations added so far.
+    pub fn annotations(&self) -> usize {
+        self.annotations.len()
+    }
+
+    /// Samples recorded so far.
+    pub fn samples_written(&self) -> usize {
+        self.samples_written
+    }
+
+    /// Appends a block. Where the source's stream jumps, as after a seek in a file, a new
+    /// capture segment starts.
+    pub fn write(&mut self, block: &SampleBlock) -> Result<(), String> {
+        let offset = self.samples_written as isize - block.start_sample as isize;
+        match self.stream_offset {
+            None => self.captures[0].datetime = Some(block.timestamp),
+            Some(previous) if previous != offset => {
+                let frequency = self.captures[self.captures.len() - 1].frequency;
+                self.start_capture(frequency, Some(block.timestamp));
+            }
+            Some(_) => {}
+        }
+        self.stream_offset = Some(offset);
+
+        let bytes: Vec<u8> = block.samples.iter().flat_map(|&sample| to_cu8(sample)).collect();
+        self.data.write_all(&bytes).map_err(|e| format!("Failed to write {}: {}", self.data_path().display(), e))?;
+        self.samples_written += block.samples.len();
+        Ok(())
+    }
+
+    /// Starts a capture segment at the next sample after the source has been retuned.
+    pub fn retune(&mut self, frequency: u32) {
+        if self.captures[self.captures.len() - 1].frequency != frequency {
+            self.start_capture(frequency, None);
+        }
+    }
+
+    fn start_capture(&mut self, frequency: u32, datetime: Option<SystemTime>) {
+        let capture = Capture { sample_start: self.samples_written, frequency, datetime };
+        // A segment without samples is replaced
+        match self.captures.last_mut() {
+            Some(last) if last.sample_start == self.samples_written => *last = capture,
+            _ => self.captures.push(capture),
+        }
+    }
+
+    /// Adds an annotation whose sample range is given in the source's stream. Ranges that
+    /// start before the recording are dropped.
+    pub fn annotate(&mut self, mut annotation: Annotation) {
+        let Some(offset) = self.stream_offset else {
+            return;
+        };
+        let Some(sample_start) = annotation.sample_start.checked_add_signed(offset) else {
+            return;
+        };
+        annotation.sample_start = sample_start;
+        self.annotations.push(annotation);
+    }
+
+    /// Flushes the data and writes the metadata.
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.finished = true;
+        self.data.flush().map_err(|e| format!("Failed to write {}: {}", self.data_path().display(), e))?;
+        let meta_path = self.meta_path();
+        let json = serde_json::to_string_pretty(&self.metadata()).map_err(|e| e.to_string())?;
+        std::fs::write(&meta_path, json).map_err(|e| format!("Failed to write {}: {}", meta_path.display(), e))
+    }
+
+    fn metadata(&self) -> Value {
+        let mut global = Map::new();
+        global.insert("core:datatype".to_string(), json!("cu8"));
+        global.insert("core:sample_rate".to_string(), json!(self.info.sample_rate));
+        global.insert("core:version".to_string(), json!("1.0.0"));
+        global.insert("core:hw".to_string(), json!(self.info.hardware));
+        global.insert("core:recorder".to_string(), json!(format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))));
+        global.insert("core:extensions".to_string(), json!([{ "name": EXTENSION, "version": "1.0.0", "optional": true }]));
+        global.insert(format!("{}:gain_db", EXTENSION), json!(self.info.gain as f32 / 10.0));
+        global.insert(format!("{}:ppm", EXTENSION), json!(self.info.ppm));
+        if let Some(ref serial) = self.info.serial {
+            global.insert(format!("{}:serial", EXTENSION), json!(serial));
+        }
+
+        let captures: Vec<Value> = self.captures.iter()
+            .map(|capture| {
+                let mut fields = Map::new();
+                fields.insert("core:sample_start".to_string(), json!(capture.sample_start));
+                fields.insert("core:frequency".to_string(), json!(capture.frequency));
+                if let Some(datetime) = capture.datetime {
+                    fields.insert("core:datetime".to_string(), json!(format_datetime(datetime)));
+                }
+                Value::Object(fields)
+            })
+            .collect();
+
+        let mut annotations = self.annotations.clone();
+        annotations.sort_by_key(|annotation| annotation.sample_start);
+        let annotations: Vec<Value> = annotations.iter()
+            .map(|annotation| {
+                let mut fields = Map::new();
+                fields.insert("core:sample_start".to_string(), json!(annotation.sample_start));
+                fields.insert("core:sample_count".to_string(), json!(annotation.sample_count));
+                if let (Some(lower), Some(upper)) = (annotation.freq_lower_edge, annotation.freq_upper_edge) {
+                    fields.insert("core:freq_lower_edge".to_string(), json!(lower));
+                    fields.insert("core:freq_upper_edge".to_string(), json!(upper));
+                }
+                fields.insert("core:label".to_string(), json!(annotation.label));
+                fields.insert("core:comment".to_string(), json!(annotation.comment));
+                if let Some(crc) = annotation.crc {
+                    fields.insert(format!("{}:crc", EXTENSION), json!(crc_name(crc)));
+                }
+                if let Some(ref payload) = annotation.payload {
+                    fields.insert(format!("{}:payload", EXTENSION), json!(format_hex(payload)));
+                }
+                Value::Object(fields)
+            })
+            .collect();
+
+        json!({
+            "global": global,
+            "captures": captures,
+            "annotations": annotations,
+        })
+    }
+}
+
+impl Drop for SigmfRecorder {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.finish() {
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
+
+/// What the metadata of a SigMF recording says about its samples: how to read and tune
+/// them, and the packets annotated in them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigmfMetadata {
+    pub format: SampleFormat,
+    pub sample_rate: Option<u32>,
+    pub frequency: Option<u32>,         // centre frequency of the first capture segment (Hz)
+    pub start_time: Option<SystemTime>, // capture time of the first sample
+    pub annotations: Vec<Annotation>,   // sorted by their first sample
+}
+
+impl SigmfMetadata {
+    /// Reads `<base_path>.sigmf-meta`. Annotations without a sample count reach to the end of
+    /// `<base_path>.sigmf-data`.
+    pub fn load(base_path: &Path) -> Result<Self, String> {
+        let meta_path = base_path.with_extension("sigmf-meta");
+        let text = std::fs::read_to_string(&meta_path).map_err(|e| format!("Failed to read {}: {}", meta_path.display(), e))?;
+        let meta: Value = serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", meta_path.display(), e))?;
+
+        let global = &meta["global"];
+        let format = sample_format(global["core:datatype"].as_str().ok_or("SigMF metadata has no datatype")?)?;
+        let sample_rate = global["core:sample_rate"].as_f64().map(|rate| rate.round() as u32);
+
+        let captures = meta["captures"].as_array().map_or(&[][..], Vec::as_slice);
+        let frequency = captures.first().and_then(|capture| capture["core:frequency"].as_f64()).map(|f| f.round() as u32);
+        let start_time = captures.first().and_then(|capture| capture["core:datetime"].as_str()).and_then(parse_datetime);
+        if captures.iter().skip(1).any(|capture| capture["core:frequency"].as_f64().map(|f| f.round() as u32) != frequency) {
+            eprintln!("{} was retuned during the recording, it plays back at the first frequency", meta_path.display());
+        }
+
+        let data_path = base_path.with_extension("sigmf-data");
+        let length = std::fs::metadata(&data_path).map_or(0, |data| data.len() as usize / format.sample_size());
+        let mut annotations: Vec<Annotation> = meta["annotations"].as_array().map_or(&[][..], Vec::as_slice).iter()
+            .filter_map(|annotation| {
+                let sample_start = annotation["core:sample_start"].as_u64()? as usize;
+                Some(Annotation {
+                    sample_start,
+                    sample_count: annotation["core:sample_count"].as_u64().map_or(length.saturating_sub(sample_start), |count| count as usize),
+                    freq_lower_edge: annotation["core:freq_lower_edge"].as_f64(),
+                    freq_upper_edge: annotation["core:freq_upper_edge"].as_f64(),
+                    label: annotation["core:label"].as_str().unwrap_or_default().to_string(),
+                    comment: annotation["core:comment"].as_str().unwrap_or_default().to_string(),
+                    crc: annotation[format!("{}:crc", EXTENSION)].as_str().and_then(crc_from_name),
+                    payload: annotation[format!("{}:payload", EXTENSION)].as_str().and_then(parse_hex),
+                })
+            })
+            .collect();
+        annotations.sort_by_key(|annotation| annotation.sample_start);
+
+        Ok(Self { format, sample_rate, frequency, start_time, annotations })
+    }
+
+    /// The settings to open the data file with.
+    pub fn file_settings(&self) -> FileSettings {
+        FileSettings {
+            format: Some(self.format),
+            sample_rate: self.sample_rate,
+            frequency: self.frequency,
+            start_time: self.start_time,
+        }
+    }
+}
+
+/// True for either file of a SigMF recording.
+pub fn is_sigmf(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("sigmf-meta") || e.eq_ignore_ascii_case("sigmf-data"))
+}
+
+fn crc_name(crc: CrcStatus) -> &'static str {
+    match crc {
+        CrcStatus::Ok => "ok",
+        CrcStatus::Failed => "failed",
+        CrcStatus::Disabled => "none",
+    }
+}
+
+fn crc_from_name(name: &str) -> Option<CrcStatus> {
+    [CrcStatus::Ok, CrcStatus::Failed, CrcStatus::Disabled].into_iter().find(|&crc| crc_name(crc) == name)
+}
+
+/// Bytes as compact lowercase hex, the form of `hc12:payload`.
+pub fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+/// Sample format of a SigMF datatype. Only complex little endian types have a counterpart.
+fn sample_format(datatype: &str) -> Result<SampleFormat, String> {
+    match datatype {
+        "cu8" => Ok(SampleFormat::Cu8),
+        "ci8" => Ok(SampleFormat::Cs8),
+        "ci16_le" => Ok(SampleFormat::Cs16),
+        "cf32_le" => Ok(SampleFormat::Cf32),
+        _ => Err(format!("Unsupported SigMF datatype {}", datatype)),
+    }
+}
+
+/// Inverse of `convert_cu8`, exact for samples that came from unsigned 8 bit.
+fn to_cu8(sample: Complex32) -> [u8; 2] {
+    let quantize = |x: f32| (x * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8;
+    [quantize(sample.re), quantize(sample.im)]
+}
+
+/// Time as ISO 8601 UTC with milliseconds, the form of `core:datetime`.
+pub fn format_datetime(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}T{}Z", year, month, day, format_utc(time))
+}
+
+/// Parses `core:datetime`, ISO 8601 UTC with optional fractional seconds.
+pub fn parse_datetime(text: &str) -> Option<SystemTime> {
+    let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+    let date: Vec<i64> = date.split('-').map(|field| field.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<&str> = time.split(':').collect();
+    if date.len() != 3 || time.len() != 3 || !(1..=12).contains(&date[1]) || !(1..=31).contains(&date[2]) {
+        return None;
+    }
+    let hours: u64 = time[0].parse().ok()?;
+    let minutes: u64 = time[1].parse().ok()?;
+    let seconds: f64 = time[2].parse().ok()?;
+    if hours > 23 || minutes > 59 || !(0.0..61.0).contains(&seconds) {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(date[0], date[1] as u32, date[2] as u32)).ok()?;
+    let whole = Duration::from_secs(days * 86_400 + hours * 3600 + minutes * 60);
+    Some(UNIX_EPOCH + whole + Duration::from_secs_f64(seconds))
+}
+
+/// Day count since 1970-01-01 of a date in the proleptic Gregorian calendar, the inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 } as i64; // counted from March
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of a day count since 1970-01-01 in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // counted from March
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days_round_trip() {
+        // 1600-03-01 to 2400-02-29, across century and 400 year leap rules
+        for days in -135_080..157_054 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days, "{}-{}-{}", year, month, day);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+        assert_eq!(days_from_civil(2100, 2, 28) + 1, days_from_civil(2100, 3, 1));
+        assert_eq!(days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28), 2);
+    }
+
+    #[test]
+    fn datetime_round_trip() {
+        for millis in [0u64, 951_782_400_000, 1_704_164_645_123, 4_107_542_399_999, 1_792_233_456_789] {
+            let time = UNIX_EPOCH + Duration::from_millis(millis);
+            assert_eq!(parse_datetime(&format_datetime(time)), Some(time), "{}", format_datetime(time));
+        }
+        assert_eq!(format_datetime(UNIX_EPOCH + Duration::from_millis(1_704_164_645_123)), "2024-01-02T03:04:05.123Z");
+        assert_eq!(parse_datetime("2024-01-02T03:04:05Z"), Some(UNIX_EPOCH + Duration::from_secs(1_704_164_645)));
+        assert_eq!(parse_datetime("2024-01-02T03:04:05.5Z"), Some(UNIX_EPOCH + Duration::from_millis(1_704_164_645_500)));
+    }
+
+    #[test]
+    fn datetime_rejects_malformed() {
+        for text in ["", "2024-01-02T03:04:05", "2024-01-02 03:04:05Z", "2024-13-02T03:04:05Z",
+                     "2024-01-32T03:04:05Z", "2024-01-02T24:00:00Z", "2024-01T03:04:05Z", "1969-12-31T23:59:59Z"] {
+            assert_eq!(parse_datetime(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let bytes = [0x00, 0x2D, 0xD4, 0xFF];
+        assert_eq!(format_hex(&bytes), "002dd4ff");
+        assert_eq!(parse_hex("002dd4ff"), Some(bytes.to_vec()));
+        assert_eq!(parse_hex("002DD4FF"), Some(bytes.to_vec()));
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("zz"), None);
+    }
+
+    #[test]
+    fn recording_reads_back() {
+        let base = std::env::temp_dir().join(format!("hc12_sigmf_test_{}", std::process::id()));
+        let info = RecordingInfo {
+            sample_rate: 2_048_000,
+            frequency: 433_700_000,
+            gain: 297,
+            ppm: -3,
+            serial: Some("00000001".to_string()),
+            hardware: "RTL-SDR".to_string(),
+        };
+        let start = UNIX_EPOCH + Duration::from_millis(1_792_233_456_789);
+        let block = |start_sample: usize, timestamp: SystemTime| SampleBlock {
+            start_sample,
+            timestamp,
+            samples: (0..1000).map(|n| Complex32::from_polar(0.5, n as f32 * 0.1)).collect(),
+        };
+        let annotation = |sample_start: usize, payload: &[u8]| Annotation {
+            sample_start,
+            sample_count: 400,
+            freq_lower_edge: Some(433_377_500.0),
+            freq_upper_edge: Some(433_422_500.0),
+            label: "HC-12 CH001".to_string(),
+            comment: String::from_utf8_lossy(payload).to_string(),
+            crc: Some(CrcStatus::Ok),
+            payload: Some(payload.to_vec()),
+        };
+
+        let mut recorder = SigmfRecorder::create(&base, info).unwrap();
+        // The source stream is 5000 samples in when the recording starts
+        recorder.write(&block(5000, start)).unwrap();
+        recorder.annotate(annotation(5600, b"second"));
+        recorder.annotate(annotation(5100, b"first"));
+        recorder.annotate(annotation(4000, b"before the recording"));
+        recorder.write(&block(6000, start + Duration::from_millis(1))).unwrap();
+        recorder.finish().unwrap();
+        assert_eq!(recorder.annotations(), 2);
+        assert_eq!(std::fs::metadata(recorder.data_path()).unwrap().len(), 2 * 2000);
+
+        let metadata = SigmfMetadata::load(&recorder.meta_path()).unwrap();
+        assert_eq!(metadata.format, SampleFormat::Cu8);
+        assert_eq!(metadata.sample_rate, Some(2_048_000));
+        assert_eq!(metadata.frequency, Some(433_700_000));
+        assert_eq!(metadata.start_time, Some(start));
+        assert_eq!(metadata.annotations, vec![annotation(100, b"first"), annotation(600, b"second")]);
+        assert_eq!(metadata.file_settings().format, Some(SampleFormat::Cu8));
+
+        std::fs::remove_file(recorder.data_path()).ok();
+        std::fs::remove_file(recorder.meta_path()).ok();
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use crate::constants;
+
+/// Hard decision slicer with a threshold halfway between the tracked high and low symbol levels.
+///
+/// While the framer searches for a preamble both levels are measured over the last `window`
+/// symbols. Once the preamble is found they are tracked decision directed until the packet ends.
+/// With the discriminator the threshold is the residual carrier offset in Hz, which the
+/// decoder's AFC reports and feeds back into its coarse mixer.
+pub struct Slicer {
+    window: usize,
+    history: VecDeque<f32>, // last symbols seen while searching
+    high: f32,              // mean level of '1' symbols
+    low: f32,               // mean level of '0' symbols
+    noise: f32,             // noise standard deviation over the preamble, in soft value units
+    locked: bool,
+}
+
+impl Slicer {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(2),
+            history: VecDeque::with_capacity(window.max(2)),
+            high: 0.0,
+            low: 0.0,
+            noise: 0.0,
+            locked: false,
+        }
+    }
+
+    /// Forgets the measured levels.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.window);
+    }
+
+    /// Decides one symbol and returns its soft value: the distance from the threshold in units of
+    /// half the level spacing, so a clean symbol gives ±1 and the sign is the hard bit.
+    /// `searching` is true while no preamble has been detected.
+    pub fn decide(&mut self, value: f32, searching: bool) -> f32 {
+        if searching {
+            self.locked = false;
+            if self.history.len() == self.window {
+                self.history.pop_front();
+            }
+            self.history.push_back(value);
+            self.measure_levels();
+        } else {
+            self.locked = true;
+        }
+
+        let soft = (value - self.threshold()) / ((self.high - self.low) / 2.0).max(f32::EPSILON);
+        if self.locked {
+            let level = if soft > 0.0 { &mut self.high } else { &mut self.low };
+            *level += constants::SLICER_TRACK_GAIN * (value - *level);
+        }
+        soft
+    }
+
+    pub fn threshold(&self) -> f32 {
+        (self.high + self.low) / 2.0
+    }
+
+    /// Noise measured over the preamble, relative to half the level spacing. The preamble's
+    /// intersymbol interference is the same for every bit, so the spread around the levels is noise.
+    pub fn noise(&self) -> f32 {
+        self.noise
+    }
+
+    /// Splits the window at its mean and averages both halves.
+    fn measure_levels(&mut self) {
+        let mean = self.history.iter().sum::<f32>() / self.history.len() as f32;
+        let (mut high, mut high_count, mut low, mut low_count) = (0.0, 0, 0.0, 0);
+        for &v in &self.history {
+            if v > mean {
+                high += v;
+                high_count += 1;
+            } else {
+                low += v;
+                low_count += 1;
+            }
+        }
+        self.high = if high_count > 0 { high / high_count as f32 } else { mean };
+        self.low = if low_count > 0 { low / low_count as f32 } else { mean };
+
+        let variance = self.history.iter()
+            .map(|&v| (v - if v > mean { self.high } else { self.low }).powi(2))
+            .sum::<f32>() / self.history.len() as f32;
+        self.noise = variance.sqrt() / ((self.high - self.low) / 2.0).max(f32::EPSILON);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{PacketConfig, PacketFramer};
+
+    /// Maps bits to discriminator levels around `offset`, with mark below space if `inverted`.
+    fn levels(bits: &[bool], offset: f32, deviation: f32, inverted: bool) -> Vec<f32> {
+        bits.iter().map(|&bit| offset + if bit != inverted { deviation } else { -deviation }).collect()
+    }
+
+    #[test]
+    fn measures_preamble_levels() {
+        let bits: Vec<bool> = (0..64).map(|i| i % 2 == 0).collect();
+        let mut slicer = Slicer::new(32);
+        let mut state = 1u32;
+        for (i, (&value, &bit)) in levels(&bits, 3000.0, 15000.0, false).iter().zip(&bits).enumerate() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let noise = (state as f32 / u32::MAX as f32 - 0.5) * 1000.0;
+            let soft = slicer.decide(value + noise, true);
+            // the first symbol alone sets both levels
+            if i > 0 {
+                assert_eq!(soft > 0.0, bit);
+            }
+        }
+        assert!((slicer.threshold() - 3000.0).abs() < 200.0, "threshold {}", slicer.threshold());
+        assert!(slicer.noise() > 0.0 && slicer.noise() < 0.05, "noise {}", slicer.noise());
+        assert!((slicer.decide(18000.0, false) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn detects_inverted_polarity() {
+        let config = PacketConfig::default();
+        let bits = config.encode(b"inverted");
+        let mut slicer = Slicer::new(config.preamble_min_bits);
+        let mut framer = PacketFramer::new(config);
+        let packets: Vec<_> = levels(&bits, -2000.0, 15000.0, true).iter()
+            .enumerate()
+            .filter_map(|(i, &value)| {
+                let soft = slicer.decide(value, framer.is_searching());
+                framer.push(soft, i)
+            })
+            .collect();
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].inverted);
+        assert_eq!(packets[0].payload, b"inverted");
+        assert!((slicer.threshold() + 2000.0).abs() < 1.0, "threshold {}", slicer.threshold());
+    }
+}
@@ -0,0 +1,300 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use num_complex::Complex32;
+
+/// A buffer of IQ samples as delivered by a source.
+#[derive(Debug, Clone)]
+pub struct SampleBlock {
+    pub start_sample: usize,  // stream index of `samples[0]`, counted from the start of the source
+    pub timestamp: SystemTime, // time `samples[0]` was received or, for recordings, captured
+    pub samples: Vec<Complex32>,
+}
+
+/// Where IQ samples come from. Sources deliver blocks without blocking and take the tuning
+/// commands the app sends; a source that cannot act on a command ignores it.
+pub trait SampleSource {
+    /// Next block if one is ready.
+    fn next_block(&mut self) -> Option<SampleBlock>;
+
+    /// Centre frequency the source is tuned to (Hz).
+    fn set_frequency(&mut self, frequency: u32);
+
+    fn set_sample_rate(&mut self, rate: u32);
+
+    /// Tuner gain in tenths of a dB.
+    fn set_gain(&mut self, gain: i32);
+
+    /// Crystal correction in ppm, positive if the crystal runs fast.
+    fn set_frequency_correction(&mut self, ppm: i32);
+
+    /// True while the source delivers samples.
+    fn is_running(&self) -> bool;
+
+    /// Serial number of the device behind the source, if it has one.
+    fn serial(&self) -> Option<String>;
+
+    /// Short description for the status display.
+    fn name(&self) -> String;
+
+    /// Sample rate of a recording, which the app has to follow. `None` for live sources.
+    fn recorded_sample_rate(&self) -> Option<u32> {
+        None
+    }
+
+    /// Centre frequency a recording was made at (Hz), if it is known. Recordings cannot be retuned.
+    fn recorded_frequency(&self) -> Option<u32> {
+        None
+    }
+
+    /// Transport controls, for sources that play back a recording.
+    fn playback(&mut self) -> Option<&mut Playback> {
+        None
+    }
+}
+
+/// The available sample sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceKind {
+    RtlSdr,
+    RtlTcp,
+    File,
+    Synthetic,
+}
+
+impl SourceKind {
+    pub const ALL: [SourceKind; 4] = [SourceKind::RtlSdr, SourceKind::RtlTcp, SourceKind::File, SourceKind::Synthetic];
+
+    pub fn as_string(self) -> String {
+        match self {
+            SourceKind::RtlSdr => "RTL-SDR".to_string(),
+            SourceKind::RtlTcp => "rtl_tcp".to_string(),
+            SourceKind::File => "IQ file".to_string(),
+            SourceKind::Synthetic => "Synthetic HC-12".to_string(),
+        }
+    }
+}
+
+/// Releases the samples of a recorded or generated stream in step with the wall clock,
+/// optionally faster or slower than real time.
+pub struct Pacer {
+    started: Instant, // time the first sample was due
+    sample_rate: u32,
+    speed: f32,       // playback speed relative to real time
+    released: usize,  // stream index of the next sample
+}
+
+impl Pacer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            started: Instant::now(),
+            sample_rate: sample_rate.max(1),
+            speed: 1.0,
+            released: 0,
+        }
+    }
+
+    /// True once the wall clock has caught up with the samples released so far.
+    pub fn is_due(&self) -> bool {
+        self.started.elapsed().as_secs_f64() * self.sample_rate as f64 * self.speed as f64 >= self.released as f64
+    }
+
+    pub fn release(&mut self, samples: usize) {
+        self.released += samples;
+    }
+
+    pub fn released(&self) -> usize {
+        self.released
+    }
+
+    /// Stream time of the samples released so far.
+    pub fn position(&self) -> Duration {
+        Duration::from_secs_f64(self.released as f64 / self.sample_rate as f64)
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Changes the rate from now on, without a jump in the release schedule.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.max(1);
+        self.resync();
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(f32::EPSILON);
+        self.resync();
+    }
+
+    /// Continues from `sample` as if the stream had been released up to there.
+    pub fn seek(&mut self, sample: usize) {
+        self.released = sample;
+        self.resync();
+    }
+
+    /// Schedules the next sample for now, after the stream has been held.
+    pub fn resync(&mut self) {
+        let ahead = Duration::from_secs_f64(self.released as f64 / (self.sample_rate as f64 * self.speed as f64));
+        self.started = Instant::now().checked_sub(ahead).unwrap_or_else(Instant::now);
+    }
+}
+
+/// Transport controls of a recording. The GUI operates them, the source asks `is_due`
+/// before each block and applies a requested seek.
+pub struct Playback {
+    pacer: Pacer,
+    length: usize,        // samples in the recording
+    paused: bool,
+    looping: bool,
+    step: bool,           // release one block while paused
+    seek: Option<usize>,  // position requested, not yet applied by the source
+}
+
+impl Playback {
+    pub fn new(sample_rate: u32, length: usize) -> Self {
+        Self {
+            pacer: Pacer::new(sample_rate),
+            length,
+            paused: false,
+            looping: false,
+            step: false,
+            seek: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn play(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.pacer.resync();
+        }
+    }
+
+    /// Releases the next block while paused.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.pacer.speed()
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.pacer.set_speed(speed);
+    }
+
+    /// Requests playback to continue at `sample`.
+    pub fn seek(&mut self, sample: usize) {
+        self.seek = Some(sample.min(self.length));
+    }
+
+    /// Stream index of the next sample.
+    pub fn position(&self) -> usize {
+        self.seek.unwrap_or(self.pacer.released())
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Stream time of the next sample.
+    pub fn time(&self) -> Duration {
+        self.pacer.position()
+    }
+
+    /// True if the source may release a block now.
+    pub fn is_due(&mut self) -> bool {
+        if std::mem::take(&mut self.step) {
+            return true;
+        }
+        !self.paused && self.pacer.is_due()
+    }
+
+    /// Position the source has to move to before its next block, if a seek was requested.
+    pub fn take_seek(&mut self) -> Option<usize> {
+        let seek = self.seek.take()?;
+        self.pacer.seek(seek);
+        Some(seek)
+    }
+
+    /// Records a block of `samples` as released.
+    pub fn release(&mut self, samples: usize) {
+        self.pacer.release(samples);
+    }
+}
+
+/// Converts interleaved unsigned 8 bit I and Q, as the RTL-SDR delivers them, to complex
+/// samples, mapping the range 0 ... 255 to -1.0 ... +1.0.
+pub fn convert_cu8(buffer: &[u8]) -> Vec<Complex32> {
+    buffer.chunks_exact(2)
+        .map(|chunk| {
+            let i = (chunk[0] as f32 - 127.5) / 127.5;
+            let q = (chunk[1] as f32 - 127.5) / 127.5;
+            Complex32::new(i, q)
+        })
+        .collect()
+}
+
+/// Time of day as `HH:MM:SS.mmm` UTC.
+pub fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() % 86_400;
+    format!("{:02}:{:02}:{:02}.{:03}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            since_epoch.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_paces_and_steps() {
+        let mut playback = Playback::new(1000, 10_000);
+        assert!(playback.is_due());
+        // Five seconds ahead of the clock
+        playback.release(5000);
+        assert!(!playback.is_due());
+
+        playback.pause();
+        playback.step();
+        assert!(playback.is_due());
+        assert!(!playback.is_due());
+        // Resumed without making up for the time paused
+        playback.play();
+        assert!(playback.is_due());
+    }
+
+    #[test]
+    fn playback_seeks() {
+        let mut playback = Playback::new(1000, 10_000);
+        playback.release(2000);
+        playback.seek(500);
+        assert_eq!(playback.position(), 500);
+        assert_eq!(playback.take_seek(), Some(500));
+        assert_eq!(playback.take_seek(), None);
+        assert_eq!(playback.time(), Duration::from_millis(500));
+        // Resumed from there on the clock
+        assert!(playback.is_due());
+
+        playback.seek(20_000);
+        assert_eq!(playback.take_seek(), Some(10_000));
+        assert_eq!(playback.position(), 10_000);
+    }
+}
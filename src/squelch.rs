@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use num_complex::Complex32;
+use crate::constants;
+use crate::power::to_dbfs;
+
+/// Squelch settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SquelchConfig {
+    pub enabled: bool,  // only feed bursts to the decoder
+    pub margin_db: f32, // power above the noise floor that opens the squelch
+}
+
+impl Default for SquelchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            margin_db: constants::SQUELCH_DEFAULT_MARGIN_DB,
+        }
+    }
+}
+
+/// A burst of energy seen by the squelch, whether it could be decoded or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Burst {
+    pub start_sample: usize,     // stream index of the first block above the threshold
+    pub duration: usize,         // samples until the power dropped below the threshold
+    pub peak_power_dbfs: f32,    // highest block power during the burst
+    pub freq_offset: f32,        // power weighted mean frequency relative to the tuned centre (Hz)
+    pub samples: Vec<Complex32>, // leading samples, up to `BURST_CAPTURE_S`
+    pub channel: Option<u8>,     // HC-12 channel the burst was seen on
+}
+
+/// Contiguous stream samples passed on to the decoder.
+#[derive(Debug, Clone)]
+pub struct BurstSamples {
+    pub start_sample: usize, // stream index of `samples[0]`
+    pub samples: Vec<Complex32>,
+}
+
+/// Result of one buffer through the squelch.
+#[derive(Debug, Default)]
+pub struct SquelchOutput {
+    pub segments: Vec<BurstSamples>, // samples to demodulate, in stream order
+    pub bursts: Vec<Burst>,          // bursts that ended in this buffer
+}
+
+/// Energy detector ahead of the demodulator. The wideband power is measured in blocks of
+/// `POWER_BLOCK_SIZE` samples and compared against a noise floor tracked while the squelch is
+/// closed. A burst opens when a block exceeds the floor by the margin, includes a short pre-roll
+/// so the decoder's filters settle before the preamble, and closes after a hangover below it.
+pub struct BurstDetector {
+    sample_rate: f32,
+    config: SquelchConfig,
+    block: Vec<Complex32>,          // samples of the block being measured
+    block_start: usize,             // stream index of `block[0]`
+    preroll: VecDeque<Complex32>,   // most recent samples while closed
+    noise_floor: f32,               // mean block power while closed, NaN until the first block
+    burst: Option<Burst>,           // burst in progress
+    quiet: usize,                   // samples below the threshold since the burst was last active
+    pulse_pair: Complex32,          // sum of x[n]·conj(x[n-1]) over the burst
+    capture: usize,                 // number of leading samples kept per burst
+    last_sample: Complex32,
+}
+
+impl BurstDetector {
+    pub fn new(sample_rate: f32, config: SquelchConfig) -> Self {
+        Self {
+            sample_rate,
+            config,
+            block: Vec::with_capacity(constants::POWER_BLOCK_SIZE),
+            block_start: 0,
+            preroll: VecDeque::new(),
+            noise_floor: f32::NAN,
+            burst: None,
+            quiet: 0,
+            pulse_pair: Complex32::new(0.0, 0.0),
+            capture: (constants::BURST_CAPTURE_S * sample_rate) as usize,
+            last_sample: Complex32::new(0.0, 0.0),
+        }
+    }
+
+    pub fn set_config(&mut self, config: SquelchConfig) {
+        self.config = config;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.burst.is_some()
+    }
+
+    /// Noise floor of the wideband input (dBFS).
+    pub fn noise_floor_dbfs(&self) -> f32 {
+        to_dbfs(self.noise_floor)
+    }
+
+    /// Runs the next buffer of the stream through the squelch. Samples are released once their
+    /// block has been measured, the tail of the buffer is held until the next call. With the
+    /// squelch disabled bursts are still recorded, but every sample is passed on.
+    pub fn process(&mut self, iq: &[Complex32]) -> SquelchOutput {
+        let mut output = SquelchOutput::default();
+        for &sample in iq {
+            if self.burst.is_some() {
+                self.pulse_pair += sample * self.last_sample.conj();
+            }
+            self.last_sample = sample;
+
+            self.block.push(sample);
+            if self.block.len() == constants::POWER_BLOCK_SIZE {
+                self.end_block(&mut output);
+            }
+        }
+        output
+    }
+
+    fn end_block(&mut self, output: &mut SquelchOutput) {
+        let block = std::mem::replace(&mut self.block, Vec::with_capacity(constants::POWER_BLOCK_SIZE));
+        let block_start = self.block_start;
+        self.block_start += block.len();
+        let power = block.iter().map(|s| s.norm_sqr()).sum::<f32>() / block.len() as f32;
+
+        if self.noise_floor.is_nan() {
+            self.noise_floor = power;
+        }
+        let open_level = self.noise_floor * 10.0f32.powf(self.config.margin_db / 10.0);
+        let close_level = open_level * 10.0f32.powf(-constants::SQUELCH_HYSTERESIS_DB / 10.0);
+
+        let was_open = self.burst.is_some();
+        match self.burst {
+            None if power > open_level => {
+                self.burst = Some(Burst {
+                    start_sample: block_start,
+                    duration: block.len(),
+                    peak_power_dbfs: to_dbfs(power),
+                    freq_offset: 0.0,
+                    samples: Vec::new(),
+                    channel: None,
+                });
+                self.quiet = 0;
+                // The pulse pair sum starts with the samples of this block
+                self.pulse_pair = block.windows(2).map(|w| w[1] * w[0].conj()).sum();
+            }
+            None => {
+                self.noise_floor += constants::SQUELCH_FLOOR_GAIN * (power - self.noise_floor);
+            }
+            Some(ref mut burst) => {
+                burst.peak_power_dbfs = burst.peak_power_dbfs.max(to_dbfs(power));
+                if power > close_level {
+                    self.quiet = 0;
+                    burst.duration = block_start + block.len() - burst.start_sample;
+                } else {
+                    self.quiet += block.len();
+                }
+            }
+        }
+
+        if let Some(ref mut burst) = self.burst {
+            let take = self.capture.saturating_sub(burst.samples.len()).min(block.len());
+            burst.samples.extend_from_slice(&block[..take]);
+        }
+
+        // Close after the hangover, or re-baseline the floor if the channel stays busy for too long
+        let hangover = (constants::SQUELCH_HANGOVER_S * self.sample_rate) as usize;
+        let max_duration = (constants::SQUELCH_MAX_BURST_S * self.sample_rate) as usize;
+        if let Some(mut burst) = self.burst.take() {
+            let elapsed = block_start + block.len() - burst.start_sample;
+            if self.quiet >= hangover || elapsed >= max_duration {
+                if elapsed >= max_duration {
+                    self.noise_floor = power;
+                }
+                burst.freq_offset = self.pulse_pair.arg() * self.sample_rate / (2.0 * PI);
+                burst.samples.truncate(burst.duration);
+                output.bursts.push(burst);
+            } else {
+                self.burst = Some(burst);
+            }
+        }
+
+        // The block that closes a burst is still passed on, so the decoder can flush its filters
+        let is_open = self.burst.is_some();
+        if is_open && !was_open && self.config.enabled {
+            let preroll: Vec<Complex32> = self.preroll.drain(..).collect();
+            Self::emit(output, block_start - preroll.len(), &preroll);
+        }
+        if is_open || was_open || !self.config.enabled {
+            Self::emit(output, block_start, &block);
+        } else {
+            let capacity = (constants::SQUELCH_PREROLL_S * self.sample_rate) as usize;
+            self.preroll.extend(block);
+            let excess = self.preroll.len().saturating_sub(capacity);
+            self.preroll.drain(..excess);
+        }
+    }
+
+    /// Appends samples to the output, extending the last segment if they follow on from it.
+    fn emit(output: &mut SquelchOutput, start_sample: usize, samples: &[Complex32]) {
+        if samples.is_empty() {
+            return;
+        }
+        match output.segments.last_mut() {
+            Some(segment) if segment.start_sample + segment.samples.len() == start_sample => {
+                segment.samples.extend_from_slice(samples);
+            }
+            _ => output.segments.push(BurstSamples { start_sample, samples: samples.to_vec() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 128_000.0; // pre-roll and hangover of 4 blocks each
+
+    /// Low level noise with a carrier of amplitude 0.5 over `burst`.
+    fn signal(length: usize, burst: std::ops::Range<usize>) -> Vec<Complex32> {
+        let mut state = 0x2545_F491u32;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        };
+        (0..length)
+            .map(|n| {
+                let noise = Complex32::new(uniform(), uniform()) * 0.01;
+                if burst.contains(&n) { noise + Complex32::from_polar(0.5, n as f32 * 0.3) } else { noise }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stays_closed_in_noise() {
+        let mut squelch = BurstDetector::new(SAMPLE_RATE, SquelchConfig::default());
+        let output = squelch.process(&signal(64_000, 0..0));
+        assert!(output.bursts.is_empty());
+        assert!(output.segments.is_empty());
+        assert!(!squelch.is_open());
+    }
+
+    #[test]
+    fn opens_and_closes_after_hangover() {
+        let block = constants::POWER_BLOCK_SIZE;
+        let iq = signal(200 * block, 100 * block..120 * block);
+        let mut squelch = BurstDetector::new(SAMPLE_RATE, SquelchConfig::default());
+        let mut bursts = Vec::new();
+        let mut segments: Vec<BurstSamples> = Vec::new();
+        for chunk in iq.chunks(1000) {
+            let output = squelch.process(chunk);
+            bursts.extend(output.bursts);
+            segments.extend(output.segments);
+        }
+        assert!(!squelch.is_open());
+
+        assert_eq!(bursts.len(), 1);
+        assert_eq!(bursts[0].start_sample, 100 * block);
+        assert_eq!(bursts[0].duration, 20 * block);
+        assert_eq!(bursts[0].samples, iq[100 * block..120 * block]);
+        assert!(bursts[0].peak_power_dbfs > -7.0);
+
+        // Pre-roll, burst and hangover follow on from each other
+        let preroll = (constants::SQUELCH_PREROLL_S * SAMPLE_RATE) as usize;
+        let hangover = (constants::SQUELCH_HANGOVER_S * SAMPLE_RATE) as usize;
+        assert_eq!(segments[0].start_sample, 100 * block - preroll);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].start_sample + pair[0].samples.len(), pair[1].start_sample);
+        }
+        let last = segments.last().unwrap();
+        assert_eq!(last.start_sample + last.samples.len(), 120 * block + hangover);
+    }
+
+    #[test]
+    fn preroll_is_one_segment() {
+        let block = constants::POWER_BLOCK_SIZE;
+        let iq = signal(200 * block, 100 * block..120 * block);
+        let mut squelch = BurstDetector::new(SAMPLE_RATE, SquelchConfig::default());
+        let output = squelch.process(&iq);
+        let preroll = (constants::SQUELCH_PREROLL_S * SAMPLE_RATE) as usize;
+        let hangover = (constants::SQUELCH_HANGOVER_S * SAMPLE_RATE) as usize;
+        assert_eq!(output.segments.len(), 1);
+        assert_eq!(output.segments[0].start_sample, 100 * block - preroll);
+        assert_eq!(output.segments[0].samples, iq[100 * block - preroll..120 * block + hangover]);
+    }
+
+    #[test]
+    fn single_block_spike() {
+        let block = constants::POWER_BLOCK_SIZE;
+        let iq = signal(200 * block, 100 * block..101 * block);
+        let mut squelch = BurstDetector::new(SAMPLE_RATE, SquelchConfig::default());
+        let output = squelch.process(&iq);
+        assert_eq!(output.bursts.len(), 1);
+        assert_eq!(output.bursts[0].start_sample, 100 * block);
+        assert_eq!(output.bursts[0].duration, block);
+        assert_eq!(output.bursts[0].samples.len(), block);
+        assert_eq!(output.segments.len(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+use std::f32::consts::PI;
+use std::time::SystemTime;
+use num_complex::Complex32;
+use crate::constants;
+use crate::hc12::Hc12Config;
+use crate::packet::{erfc, PacketConfig};
+use crate::source::{Pacer, SampleBlock, SampleSource};
+
+/// Simulated HC-12 that sends a numbered test packet every `SYNTHETIC_PACKET_INTERVAL_S`,
+/// GFSK modulated the way the SI4463 does it, in white noise. Useful to try settings
+/// without hardware; the packets decode with the configuration the source was opened with.
+pub struct SyntheticSource {
+    frequency: u32,                // transmitter frequency (Hz)
+    hc12: Hc12Config,
+    packet_config: PacketConfig,
+    sample_rate: u32,
+    tuned_frequency: u32,          // frequency the simulated dongle is tuned to (Hz)
+    pacer: Pacer,
+    start_time: SystemTime,
+    phase: f32,                    // carrier phase (rad)
+    burst: Vec<f32>,               // frequency deviation of the packet being sent, per sample (Hz)
+    burst_position: usize,
+    next_packet: usize,            // stream index of the next packet's first sample
+    packets_sent: usize,
+    rng_state: u64,
+}
+
+impl SyntheticSource {
+    pub fn new(frequency: u32, hc12: Hc12Config, packet_config: PacketConfig, sample_rate: u32) -> Self {
+        Self {
+            frequency,
+            hc12,
+            packet_config,
+            sample_rate,
+            tuned_frequency: frequency,
+            pacer: Pacer::new(sample_rate),
+            start_time: SystemTime::now(),
+            phase: 0.0,
+            burst: Vec::new(),
+            burst_position: 0,
+            next_packet: Self::packet_interval(sample_rate),
+            packets_sent: 0,
+            rng_state: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+
+    /// Samples from one packet to the next. The first packet follows after the same time, so a
+    /// receiver can measure the noise floor before it.
+    fn packet_interval(sample_rate: u32) -> usize {
+        (constants::SYNTHETIC_PACKET_INTERVAL_S * sample_rate as f32) as usize
+    }
+
+    /// Frequency deviation over time for a frame. Each bit's rectangle is smoothed by the
+    /// Gaussian filter, i.e. it contributes the difference of two Gaussian CDFs, one bit apart.
+    fn modulate(&self, bits: &[bool]) -> Vec<f32> {
+        let samples_per_bit = self.sample_rate as f32 / self.hc12.air_rate().as_value() as f32;
+        let deviation = self.hc12.deviation();
+        let sigma = 2.0f32.ln().sqrt() / (2.0 * PI * constants::HC12_GAUSSIAN_BT); // in bits
+        let cdf = |x: f32| 0.5 * erfc(-x / (sigma * 2.0f32.sqrt()));
+        let span = (3.0 * sigma).ceil() as i64 + 1;
+
+        // One bit of ramp on either side of the frame
+        let length = ((bits.len() + 2) as f32 * samples_per_bit) as usize;
+        (0..length)
+            .map(|k| {
+                let u = k as f32 / samples_per_bit - 1.0; // time in bits from the first bit
+                let nearest = u.floor() as i64;
+                let sum: f32 = (nearest - span..=nearest + span)
+                    .filter(|&j| j >= 0 && (j as usize) < bits.len())
+                    .map(|j| {
+                        let level = if bits[j as usize] { 1.0 } else { -1.0 };
+                        level * (cdf(u - j as f32) - cdf(u - j as f32 - 1.0))
+                    })
+                    .sum();
+                deviation * sum
+            })
+            .collect()
+    }
+
+    /// Complex white Gaussian noise of unit power (Box-Muller on a xorshift generator).
+    fn noise(&mut self) -> Complex32 {
+        let mut uniform = || {
+            self.rng_state ^= self.rng_state << 13;
+            self.rng_state ^= self.rng_state >> 7;
+            self.rng_state ^= self.rng_state << 17;
+            ((self.rng_state >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+        };
+        let radius = (-uniform().ln()).sqrt();
+        Complex32::from_polar(radius, 2.0 * PI * uniform())
+    }
+}
+
+impl SampleSource for SyntheticSource {
+    fn next_block(&mut self) -> Option<SampleBlock> {
+        if !self.pacer.is_due() {
+            return None;
+        }
+        let amplitude = 10.0f32.powf(constants::SYNTHETIC_SIGNAL_DBFS / 20.0);
+        let noise_amplitude = 10.0f32.powf(constants::SYNTHETIC_NOISE_DBFS / 20.0);
+        let offset = self.frequency as f32 - self.tuned_frequency as f32;
+        let interval = Self::packet_interval(self.sample_rate);
+
+        let start_sample = self.pacer.released();
+        let mut samples = Vec::with_capacity(constants::SDR_BUFFER_SIZE / 2);
+        for n in start_sample..start_sample + constants::SDR_BUFFER_SIZE / 2 {
+            if n == self.next_packet {
+                let payload = format!("HC-12 test {}", self.packets_sent);
+                self.burst = self.modulate(&self.packet_config.encode(payload.as_bytes()));
+                self.burst_position = 0;
+                self.next_packet += interval;
+                self.packets_sent += 1;
+            }
+
+            let signal = if self.burst_position < self.burst.len() {
+                self.phase += 2.0 * PI * (offset + self.burst[self.burst_position]) / self.sample_rate as f32;
+                self.phase %= 2.0 * PI;
+                self.burst_position += 1;
+                Complex32::from_polar(amplitude, self.phase)
+            } else {
+                Complex32::new(0.0, 0.0)
+            };
+            samples.push(signal + self.noise() * noise_amplitude);
+        }
+
+        let block = SampleBlock {
+            start_sample,
+            timestamp: self.start_time + self.pacer.position(),
+            samples,
+        };
+        self.pacer.release(block.samples.len());
+        Some(block)
+    }
+
+    fn set_frequency(&mut self, frequency: u32) {
+        self.tuned_frequency = frequency;
+    }
+
+    fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate;
+        self.pacer.set_sample_rate(rate);
+        // Drop the packet being sent, it was modulated for the old rate
+        self.burst.clear();
+        self.next_packet = self.pacer.released() + Self::packet_interval(rate);
+    }
+
+    fn set_gain(&mut self, _gain: i32) {}
+
+    fn set_frequency_correction(&mut self, _ppm: i32) {}
+
+    fn is_running(&self) -> bool {
+        true
+    }
+
+    fn serial(&self) -> Option<String> {
+        None
+    }
+
+    fn name(&self) -> String {
+        format!("Synthetic HC-12 on {:.3} MHz", self.frequency as f64 / 1_000_000.0)
+    }
+}
@@ -0,0 +1,153 @@
+/// A symbol taken at the recovered symbol centre.
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    pub value: f32,
+    pub sample_index: usize, // input sample closest to the symbol centre
+    pub timing_error: f32,   // normalised Gardner error of this symbol
+}
+
+/// Gardner timing error detector with a cubic interpolator and a PI loop filter.
+pub struct SymbolSync {
+    samples_per_symbol: f32, // nominal symbol period
+    max_deviation: f32,      // largest allowed period correction (samples)
+    kp: f32,                 // proportional loop gain
+    ki: f32,                 // integral loop gain
+    period: f32,             // current period estimate
+    next: f64,               // position of the next symbol centre, relative to `history[0]`
+    history: Vec<f32>,       // tail of the previous input, needed to interpolate across calls
+    offset: usize,           // stream index of `history[0]`
+    prev_symbol: f32,
+    amplitude: f32,          // running average of |symbol|, normalises the error
+}
+
+impl SymbolSync {
+    /// `loop_bandwidth` is the normalised loop noise bandwidth (Bn·Ts), `damping` the loop damping factor.
+    pub fn new(samples_per_symbol: f32, loop_bandwidth: f32, damping: f32) -> Self {
+        // Second order loop gains, the Gardner detector gain of the normalised error is ~4.
+        let detector_gain = 4.0;
+        let theta = loop_bandwidth / (damping + 0.25 / damping);
+        let d = 1.0 + 2.0 * damping * theta + theta * theta;
+
+        Self {
+            samples_per_symbol,
+            max_deviation: samples_per_symbol * 0.02,
+            kp: 4.0 * damping * theta / d / detector_gain,
+            ki: 4.0 * theta * theta / d / detector_gain,
+            period: samples_per_symbol,
+            next: samples_per_symbol as f64 / 2.0,
+            history: Vec::new(),
+            offset: 0,
+            prev_symbol: 0.0,
+            amplitude: 0.0,
+        }
+    }
+
+    /// Returns one symbol per tracked symbol period of `input`. Consecutive calls continue
+    /// the same stream; `Symbol::sample_index` counts from the first sample ever processed.
+    pub fn process(&mut self, input: &[f32]) -> Vec<Symbol> {
+        let mut symbols = Vec::with_capacity((input.len() as f32 / self.samples_per_symbol) as usize + 1);
+
+        let mut buffer = std::mem::take(&mut self.history);
+        buffer.extend_from_slice(input);
+
+        while self.next + 2.0 < buffer.len() as f64 {
+            let mid = self.next - self.period as f64 / 2.0;
+            if mid < 1.0 {
+                self.next += self.period as f64;
+                continue;
+            }
+
+            let value = Self::interpolate(&buffer, self.next);
+            let mid_value = Self::interpolate(&buffer, mid);
+
+            // Gardner: e = (y[k-1] - y[k]) · y[k-1/2], negative when sampling late
+            self.amplitude += 0.05 * (value.abs() - self.amplitude);
+            let norm = (self.amplitude * self.amplitude).max(f32::EPSILON);
+            let error = ((self.prev_symbol - value) * mid_value / norm).clamp(-1.0, 1.0);
+            self.prev_symbol = value;
+
+            symbols.push(Symbol {
+                value,
+                sample_index: self.offset + self.next.round() as usize,
+                timing_error: error,
+            });
+
+            // PI loop filter: the integrator tracks the symbol period, the proportional path the phase
+            let correction = self.period - self.samples_per_symbol + self.ki * error * self.samples_per_symbol;
+            self.period = self.samples_per_symbol + correction.clamp(-self.max_deviation, self.max_deviation);
+            self.next += (self.period + self.kp * error * self.samples_per_symbol) as f64;
+        }
+
+        // Keep enough samples to interpolate the next symbol and its mid point
+        let keep = (self.samples_per_symbol.ceil() as usize + 4).min(buffer.len());
+        let drop = buffer.len() - keep;
+        self.history = buffer.split_off(drop);
+        self.offset += drop;
+        self.next -= drop as f64;
+        symbols
+    }
+
+    /// Cubic Lagrange interpolation of `x` at fractional index `t` (1 <= t < len - 2).
+    fn interpolate(x: &[f32], t: f64) -> f32 {
+        let i = t.floor() as usize;
+        let mu = (t - i as f64) as f32;
+        let (xm1, x0, x1, x2) = (x[i - 1], x[i], x[i + 1], x[i + 2]);
+
+        let c0 = x0;
+        let c1 = x1 - xm1 / 3.0 - x0 / 2.0 - x2 / 6.0;
+        let c2 = (xm1 + x1) / 2.0 - x0;
+        let c3 = (x2 - xm1) / 6.0 + (x0 - x1) / 2.0;
+        ((c3 * mu + c2) * mu + c1) * mu + c0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+
+    /// Random ±1 symbols of `period` samples, the first centred at `phase`, joined by raised
+    /// cosine transitions so the waveform is flat at each centre and crosses zero midway.
+    fn waveform(period: f64, phase: f64, count: usize) -> (Vec<f32>, Vec<f64>) {
+        let mut state = 7u32;
+        let symbols: Vec<f32> = (0..count + 1)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state & 1 == 1 { 1.0 } else { -1.0 }
+            })
+            .collect();
+        let samples = (phase + period * count as f64) as usize;
+        let signal = (0..samples)
+            .map(|i| {
+                let t = ((i as f64 - phase) / period).max(0.0);
+                let (k, u) = (t.floor() as usize, t.fract());
+                let blend = (1.0 - (std::f64::consts::PI * u).cos()) / 2.0;
+                symbols[k] + (symbols[k + 1] - symbols[k]) * blend as f32
+            })
+            .collect();
+        let centres = (0..count).map(|k| phase + period * k as f64).collect();
+        (signal, centres)
+    }
+
+    #[test]
+    fn locks_onto_symbol_centres() {
+        let period = 8.04; // 0.5% off the nominal period
+        let (signal, centres) = waveform(period, 3.37, 600);
+        let mut sync = SymbolSync::new(8.0, constants::TIMING_LOOP_BANDWIDTH, constants::TIMING_LOOP_DAMPING);
+        let symbols: Vec<Symbol> = signal.chunks(1000).flat_map(|chunk| sync.process(chunk)).collect();
+        assert!(symbols.len() >= 590, "{} symbols", symbols.len());
+
+        let locked = &symbols[400..];
+        for symbol in locked {
+            let distance = centres.iter().map(|c| (c - symbol.sample_index as f64).abs()).fold(f64::MAX, f64::min);
+            assert!(distance <= 1.0, "sample {} is {distance} from a centre", symbol.sample_index);
+            assert!(symbol.value.abs() > 0.9, "value {}", symbol.value);
+        }
+        let mean_error = locked.iter().map(|s| s.timing_error).sum::<f32>() / locked.len() as f32;
+        assert!(mean_error.abs() < 0.05, "mean error {mean_error}");
+        let spacing = (locked[locked.len() - 1].sample_index - locked[0].sample_index) as f64 / (locked.len() - 1) as f64;
+        assert!((spacing - period).abs() < 0.02, "spacing {spacing}");
+    }
+}
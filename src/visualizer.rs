@@ -1,23 +1,40 @@
-use egui_plot::{Line, Plot, PlotPoints, Points};
+use egui_plot::{Line, LineStyle, Plot, PlotPoint, PlotPoints, PlotUi, Points, Text, VLine};
 use rustfft::{FftPlanner, num_complex::Complex32};
-use egui;
 use crate::constants;
 
+/// A labelled stretch of a time-domain plot, such as an annotated packet. Positions are in
+/// samples of the plotted buffer and may lie outside it.
+pub struct TimeMarker {
+    pub start: f64,
+    pub end: f64,
+    pub label: String,
+    pub color: egui::Color32,
+}
+
 pub struct SignalVisualizer {
     history_size: usize,
     sample_rate: u32,
-    center_frequency: u32,
+    center_frequency: u32,        // frequency the dongle is tuned to (Hz)
+    channels: Vec<(String, u32)>, // received channels and their centre frequency (Hz)
 }
 
 impl SignalVisualizer {
     pub fn new() -> Self {
         Self {
             history_size: 8192,
-            sample_rate:  constants::SDR_SAMPLE_RATE, // TODO: get sample rate from main.
-            center_frequency: constants::SDR_DEFAULT_CENTER_FREQUENCY, // TODO: get centre frequency from main.
+            sample_rate:  constants::SDR_SAMPLE_RATE,
+            center_frequency: constants::SDR_DEFAULT_CENTER_FREQUENCY,
+            channels: Vec::new(),
         }
     }
 
+    /// Sets the tuning the spectrum axis is drawn for, and the channels to mark on it.
+    pub fn set_tuning(&mut self, center_frequency: u32, sample_rate: u32, channels: Vec<(String, u32)>) {
+        self.center_frequency = center_frequency;
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+    }
+
     pub fn plot_constellation(&self, ui: &mut egui::Ui, samples: &[Complex32]) {
         let step = samples.len().max(1) / self.history_size.min(samples.len()).max(1);
         
@@ -47,7 +64,28 @@ impl SignalVisualizer {
             });
     }
 
-    pub fn plot_magnitude(&self, ui: &mut egui::Ui, samples: &[Complex32]) {
+    /// Draws the markers as lines at either end, the start solid and labelled, `step` samples
+    /// to a plot unit. The parts outside the `length` samples plotted are left out.
+    fn draw_markers(plot_ui: &mut PlotUi, markers: &[TimeMarker], length: usize, step: usize) {
+        let top = plot_ui.plot_bounds().max()[1];
+        let visible = 0.0..=length as f64;
+        for marker in markers {
+            if visible.contains(&marker.start) {
+                let x = marker.start / step as f64;
+                plot_ui.vline(VLine::new(marker.label.as_str(), x).color(marker.color));
+                plot_ui.text(Text::new(marker.label.as_str(), PlotPoint::new(x, top), marker.label.as_str())
+                    .color(marker.color)
+                    .anchor(egui::Align2::LEFT_TOP));
+            }
+            if visible.contains(&marker.end) {
+                plot_ui.vline(VLine::new(marker.label.as_str(), marker.end / step as f64)
+                    .color(marker.color)
+                    .style(LineStyle::dashed_loose()));
+            }
+        }
+    }
+
+    pub fn plot_magnitude(&self, ui: &mut egui::Ui, samples: &[Complex32], markers: &[TimeMarker]) {
         let step = samples.len().max(1) / self.history_size.min(samples.len()).max(1);
         
         Plot::new("magnitude")
@@ -71,6 +109,7 @@ impl SignalVisualizer {
                         .color(egui::Color32::from_rgb(255, 200, 100))
                         .width(1.5)
                 );
+                Self::draw_markers(plot_ui, markers, samples.len(), step.max(1));
             });
     }
 
@@ -134,10 +173,24 @@ impl SignalVisualizer {
                         .color(egui::Color32::from_rgb(200, 100, 255))
                         .width(1.0)
                 );
+
+                // The DC spike sits at the tuned frequency, the channels are offset from it
+                plot_ui.vline(
+                    VLine::new("Tuned", self.center_frequency as f64 / 1_000_000.0)
+                        .color(egui::Color32::GRAY)
+                        .style(LineStyle::dashed_loose())
+                );
+                for (name, frequency) in &self.channels {
+                    plot_ui.vline(
+                        VLine::new(name.as_str(), *frequency as f64 / 1_000_000.0)
+                            .color(egui::Color32::from_rgb(0, 255, 0))
+                            .width(1.0)
+                    );
+                }
             });
     }
 
-    pub fn plot_fft_real(&self, ui: &mut egui::Ui, samples: &Vec<f32>) {
+    pub fn plot_fft_real(&self, ui: &mut egui::Ui, samples: &[f32]) {
 
         if samples.len() < 64 {
             ui.label("Not enough samples for FFT");
@@ -171,15 +224,13 @@ impl SignalVisualizer {
             });
     }
 
-    pub fn plot_filtered_frequency_spectrum(&self, ui: &mut egui::Ui, filtered_freq: &Vec<Complex32>) {
-        let step = filtered_freq.len().max(1) / self.history_size.min(filtered_freq.len()).max(1);
-
+    pub fn plot_filtered_frequency_spectrum(&self, ui: &mut egui::Ui, filtered_freq: &[Complex32]) {
         if filtered_freq.len() < 64 {
             ui.label("Not enough samples for FFT");
             return;
         }
 
-        let (freqs, mags) = self.compute_shifted_spectrum(&filtered_freq);
+        let (freqs, mags) = self.compute_shifted_spectrum(filtered_freq);
 
         let fft_points: Vec<[f64; 2]> = freqs.iter().zip(mags.iter())
             .map(|(&f, &m)| [f as f64, m as f64])
@@ -201,9 +252,7 @@ impl SignalVisualizer {
             });
     }
 
-    pub fn plot_instantaneous_frequency(&self, ui: &mut egui::Ui, inst_freq: &Vec<f32>) {
-        let step = inst_freq.len().max(1) / self.history_size.min(inst_freq.len()).max(1);
-
+    pub fn plot_instantaneous_frequency(&self, ui: &mut egui::Ui, inst_freq: &[f32], markers: &[TimeMarker]) {
         if inst_freq.len() < 64 {
             ui.label("Not enough samples for FFT");
             return;
@@ -228,9 +277,34 @@ impl SignalVisualizer {
                         .color(egui::Color32::from_rgb(255, 128, 0))
                         .width(1.0)
                 );
+                Self::draw_markers(plot_ui, markers, inst_freq.len(), 1);
+            });
+    }
+
+    pub fn plot_timing_error(&self, ui: &mut egui::Ui, timing_error: &[f32]) {
+        Plot::new("timing_error")
+            .width(970.0)
+            .height(200.0)
+            .include_y(-1.0)
+            .include_y(1.0)
+            .label_formatter(|_name, value| {
+                format!("Symbol #{:.0}\nError: {:.3}", value.x, value.y)
+            })
+            .show(ui, |plot_ui| {
+                let error_points: PlotPoints = timing_error.iter()
+                    .enumerate()
+                    .map(|(i, e)| [i as f64, *e as f64])
+                    .collect();
+
+                plot_ui.line(
+                    Line::new("Timing Error", error_points)
+                        .color(egui::Color32::from_rgb(100, 200, 255))
+                        .width(1.0)
+                );
             });
     }
 
+    #[allow(dead_code)]
     pub fn plot_symbols(&self, ui: &mut egui::Ui, symbols: &[u16]) {
             if symbols.is_empty() {
                 return;
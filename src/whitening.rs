@@ -0,0 +1,70 @@
+use crate::constants;
+
+/// SI4463 data whitening settings (PN9 sequence, x^9 + x^5 + 1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhiteningConfig {
+    pub enabled: bool,
+    pub seed: u16, // initial 9 bit LFSR value, loaded after the sync word
+}
+
+impl Default for WhiteningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: constants::HC12_PN9_DEFAULT_SEED,
+        }
+    }
+}
+
+/// PN9 generator producing the whitening sequence one byte at a time.
+pub struct Pn9 {
+    lfsr: u16,
+}
+
+impl Pn9 {
+    pub fn new(seed: u16) -> Self {
+        Self { lfsr: seed & 0x1FF }
+    }
+
+    /// Returns the next whitening byte and advances the LFSR by eight bits.
+    pub fn next_byte(&mut self) -> u8 {
+        let byte = self.lfsr as u8;
+        for _ in 0..8 {
+            let feedback = (self.lfsr ^ (self.lfsr >> 5)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 8);
+        }
+        byte
+    }
+
+    /// Removes (or applies, the operation is symmetric) whitening from one byte.
+    pub fn dewhiten(&mut self, byte: u8) -> u8 {
+        byte ^ self.next_byte()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pn9_sequence() {
+        // As listed for the CC1101 and SI4463 PN9 whitening with the reset seed
+        let expected = [0xFF, 0xE1, 0x1D, 0x9A, 0xED, 0x85, 0x33, 0x24, 0xEA, 0x7A, 0xD2, 0x39, 0x70, 0x97, 0x57, 0x0A];
+        let mut pn9 = Pn9::new(constants::HC12_PN9_DEFAULT_SEED);
+        let sequence: Vec<u8> = (0..expected.len()).map(|_| pn9.next_byte()).collect();
+        assert_eq!(sequence, expected);
+    }
+
+    #[test]
+    fn dewhiten_twice() {
+        let data: Vec<u8> = (0..=255).collect();
+        for seed in [constants::HC12_PN9_DEFAULT_SEED, 0x001, 0x0A5] {
+            let mut whitener = Pn9::new(seed);
+            let whitened: Vec<u8> = data.iter().map(|&b| whitener.dewhiten(b)).collect();
+            assert_ne!(whitened, data);
+            let mut dewhitener = Pn9::new(seed);
+            let restored: Vec<u8> = whitened.iter().map(|&b| dewhitener.dewhiten(b)).collect();
+            assert_eq!(restored, data);
+        }
+    }
+}